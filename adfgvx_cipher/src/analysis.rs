@@ -0,0 +1,498 @@
+//! Recovering both keys of a message produced by [`crate::encrypt_with_square`].
+//!
+//! The attack works in two stages. A monoalphabetic substitution keeps the statistics of the
+//! text it is applied to, so the right column key is the one whose digraph stream (the pairs of
+//! labels, read back in the order they were written) has the single and pair frequencies of a
+//! language rather than of noise. [`search_columns`] hill-climbs over column orders with that
+//! score. Each digraph then stands for one plaintext character, and [`solve_substitution`]
+//! finds which with simulated annealing on English trigrams.
+use utils::error::CipherError;
+use utils::ngrams::english_trigrams;
+
+use crate::square::{split_mix, PolybiusSquare};
+use crate::{untranspose_columns, Variant};
+
+/// The letters of English from the most to the least frequent, followed by the digits.
+const FREQUENCY_ORDER: &str = "ETAOINSRHLDCUMFPGWYBVKXJQZ0123456789";
+
+/// Widest column key [`search_columns`] tries every order of instead of hill-climbing.
+const EXHAUSTIVE_WIDTH: usize = 7;
+
+/// Most column keys [`search_columns`] returns.
+const MAX_COLUMN_RESULTS: usize = 20;
+
+/// How often [`solve_substitution`] reports progress during one run.
+const REPORTS_PER_RUN: usize = 20;
+
+/// How hard [`break_adfgvx`] searches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttackOptions {
+    /// Narrowest column key tried.
+    pub min_columns: usize,
+    /// Widest column key tried.
+    pub max_columns: usize,
+    /// How many times the hill climb over column orders starts again from a random order, for
+    /// every width.
+    pub column_restarts: usize,
+    /// How many of the best column keys are handed to the substitution solver.
+    pub column_candidates: usize,
+    /// How many times the annealing starts again from the best square found so far.
+    pub annealing_restarts: usize,
+    /// Steps of every annealing run.
+    pub annealing_steps: usize,
+    /// Seed of the random choices, so that runs can be repeated.
+    pub seed: u64,
+}
+
+impl Default for AttackOptions {
+    fn default() -> Self {
+        AttackOptions {
+            min_columns: 2,
+            max_columns: 10,
+            column_restarts: 30,
+            column_candidates: 3,
+            annealing_restarts: 3,
+            annealing_steps: 20_000,
+            seed: 0,
+        }
+    }
+}
+
+/// Which stage of the attack a [`Progress`] report is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Columns,
+    Substitution,
+}
+
+/// Passed to the progress callback of the attack every now and then.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub stage: Stage,
+    /// Work done so far in this stage, out of `steps`.
+    pub step: usize,
+    pub steps: usize,
+    /// Score of the best key found so far in this stage.
+    pub best_score: f64,
+}
+
+/// A possible solution, as returned by [`break_adfgvx`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub column_key: Vec<u8>,
+    pub square: PolybiusSquare,
+    pub plaintext: String,
+    /// Average log10 probability of the trigrams of `plaintext`. The higher, the more likely.
+    pub score: f64,
+}
+
+/// A tiny random number generator on top of [`split_mix`].
+struct Rng(u64);
+
+impl Rng {
+    /// A number in `0..bound`.
+    fn below(&mut self, bound: usize) -> usize {
+        (split_mix(&mut self.0) % bound as u64) as usize
+    }
+
+    /// A number in `0.0..1.0`.
+    fn unit(&mut self) -> f64 {
+        (split_mix(&mut self.0) >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+/// The ciphertext with whitespace removed, checked to be made of labels of `variant`.
+fn clean(ciphertext: &str, variant: Variant) -> Result<Vec<char>, CipherError> {
+    let labels = variant.labels();
+    let ciphertext: Vec<char> = ciphertext
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if let Some(position) = ciphertext.iter().position(|c| !labels.contains(c)) {
+        return Err(CipherError::MalformedCiphertext {
+            position,
+            reason: format!("{:?} is not a label", ciphertext[position]),
+        });
+    }
+    if !ciphertext.len().is_multiple_of(2) {
+        return Err(CipherError::MalformedCiphertext {
+            position: ciphertext.len(),
+            reason: "the length is not a multiple of 2".to_string(),
+        });
+    }
+    Ok(ciphertext)
+}
+
+/// Undoes the transposition with `column_key` and numbers every digraph by the cell it points
+/// at, row by row.
+pub fn digraphs(ciphertext: &[char], column_key: &[u8], variant: Variant) -> Vec<usize> {
+    let labels = variant.labels();
+    let label = |c: &char| labels.iter().position(|l| l == c).unwrap_or_default();
+    untranspose_columns(ciphertext, column_key)
+        .chunks(2)
+        .map(|pair| label(&pair[0]) * labels.len() + label(&pair[1]))
+        .collect()
+}
+
+/// How much the digraphs look like a substituted language rather than noise: the index of
+/// coincidence of the single digraphs plus that of consecutive pairs, each relative to what
+/// random text gives.
+pub fn column_score(digraphs: &[usize], variant: Variant) -> f64 {
+    let cells = variant.labels().len().pow(2);
+    let coincidence = |counts: &[usize], total: usize| {
+        if total < 2 {
+            return 0.0;
+        }
+        let same: usize = counts
+            .iter()
+            .map(|&count| count * count.saturating_sub(1))
+            .sum();
+        same as f64 / (total * (total - 1)) as f64
+    };
+    let mut singles = vec![0; cells];
+    for &digraph in digraphs {
+        singles[digraph] += 1;
+    }
+    let mut pairs = vec![0; cells * cells];
+    for pair in digraphs.windows(2) {
+        pairs[pair[0] * cells + pair[1]] += 1;
+    }
+    coincidence(&singles, digraphs.len()) * cells as f64
+        + coincidence(&pairs, digraphs.len().saturating_sub(1)) * (cells * cells) as f64
+}
+
+/// Searches the column keys of every width in the range of `options`: every order for keys of
+/// up to 7 columns, a hill climb that swaps or moves one column at a time for wider ones.
+/// Returns the best keys found, best first, without duplicates and at most 20 of them.
+///
+/// With an even number of columns every digraph sits in a pair of neighbouring columns, and
+/// swapping the two columns of each pair only exchanges the row and column labels. Such keys
+/// score the same and give the same plaintext, under the transposed square.
+pub fn search_columns(
+    ciphertext: &str,
+    variant: Variant,
+    options: &AttackOptions,
+    progress: &mut dyn FnMut(&Progress),
+) -> Result<Vec<(Vec<u8>, f64)>, CipherError> {
+    let ciphertext = clean(ciphertext, variant)?;
+    let mut rng = Rng(options.seed);
+    let widths: Vec<usize> = (options.min_columns.max(1)..=options.max_columns.min(255))
+        .filter(|&width| width <= ciphertext.len())
+        .collect();
+    let runs = |width: usize| {
+        if width <= EXHAUSTIVE_WIDTH {
+            1
+        } else {
+            options.column_restarts
+        }
+    };
+    let steps = widths.iter().map(|&width| runs(width)).sum();
+    let score = |key: &[u8]| column_score(&digraphs(&ciphertext, key, variant), variant);
+
+    let mut found: Vec<(Vec<u8>, f64)> = Vec::new();
+    let mut best_score = f64::NEG_INFINITY;
+    let mut step = 0;
+    for &width in &widths {
+        for _ in 0..runs(width) {
+            let mut key: Vec<u8> = (1..=width as u8).collect();
+            if width <= EXHAUSTIVE_WIDTH {
+                loop {
+                    found.push((key.clone(), score(&key)));
+                    if !next_permutation(&mut key) {
+                        break;
+                    }
+                }
+            } else {
+                for j in (1..width).rev() {
+                    key.swap(j, rng.below(j + 1));
+                }
+                let current = climb(&mut key, &score);
+                if !found.iter().any(|(other, _)| *other == key) {
+                    found.push((key, current));
+                }
+            }
+            best_score = found
+                .iter()
+                .map(|(_, score)| *score)
+                .fold(best_score, f64::max);
+            step += 1;
+            progress(&Progress {
+                stage: Stage::Columns,
+                step,
+                steps,
+                best_score,
+            });
+        }
+    }
+    found.sort_by(|a, b| b.1.total_cmp(&a.1));
+    found.truncate(MAX_COLUMN_RESULTS);
+    Ok(found)
+}
+
+/// Improves `key` by swapping two columns or moving one column elsewhere until no such change
+/// helps, returning its final score.
+fn climb(key: &mut Vec<u8>, score: &dyn Fn(&[u8]) -> f64) -> f64 {
+    let width = key.len();
+    let mut current = score(key);
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for a in 0..width {
+            for b in 0..width {
+                if a == b {
+                    continue;
+                }
+                let mut candidate = key.clone();
+                if a < b {
+                    candidate.swap(a, b);
+                } else {
+                    let column = candidate.remove(a);
+                    candidate.insert(b, column);
+                }
+                let candidate_score = score(&candidate);
+                if candidate_score > current {
+                    *key = candidate;
+                    current = candidate_score;
+                    improved = true;
+                }
+            }
+        }
+    }
+    current
+}
+
+/// Rearranges `key` into the next permutation in lexicographic order, returning false once it
+/// was the last one.
+fn next_permutation(key: &mut [u8]) -> bool {
+    let Some(i) = (1..key.len()).rev().find(|&i| key[i - 1] < key[i]) else {
+        return false;
+    };
+    let j = (i..key.len()).rev().find(|&j| key[j] > key[i - 1]).unwrap();
+    key.swap(i - 1, j);
+    key[i..].reverse();
+    true
+}
+
+/// Finds the square that turns `digraphs` into the most English-like text with simulated
+/// annealing, starting from the square that maps the most frequent digraphs to the most frequent
+/// letters. Returns the square and the total trigram score of its plaintext.
+pub fn solve_substitution(
+    digraphs: &[usize],
+    variant: Variant,
+    options: &AttackOptions,
+    progress: &mut dyn FnMut(&Progress),
+) -> (PolybiusSquare, f64) {
+    let trigrams = english_trigrams();
+    let cells = variant.labels().len().pow(2);
+    let mut rng = Rng(options.seed);
+
+    let mut counts = vec![0; cells];
+    for &digraph in digraphs {
+        counts[digraph] += 1;
+    }
+    let mut by_count: Vec<usize> = (0..cells).collect();
+    by_count.sort_by_key(|&cell| std::cmp::Reverse(counts[cell]));
+    let mut key = vec![' '; cells];
+    for (cell, symbol) in by_count.into_iter().zip(
+        FREQUENCY_ORDER
+            .chars()
+            .filter(|c| variant.charset().contains(*c)),
+    ) {
+        key[cell] = symbol;
+    }
+    let mut occurrences = vec![Vec::new(); cells];
+    for (position, &digraph) in digraphs.iter().enumerate() {
+        occurrences[digraph].push(position);
+    }
+
+    let mut text: Vec<char> = digraphs.iter().map(|&digraph| key[digraph]).collect();
+    let mut best_key = key.clone();
+    let mut best_score = trigrams.score(&text);
+    // Swapping two rare letters changes the score by a few tenths, two common ones by tens.
+    let start_temperature = (digraphs.len() as f64 / 40.0).max(1.0);
+    let steps = options.annealing_restarts * options.annealing_steps;
+    let report_every = (options.annealing_steps / REPORTS_PER_RUN).max(1);
+    for restart in 0..options.annealing_restarts {
+        key.clone_from(&best_key);
+        if restart > 0 {
+            for _ in 0..cells / 4 {
+                key.swap(rng.below(cells), rng.below(cells));
+            }
+        }
+        for (digraph, positions) in occurrences.iter().enumerate() {
+            for &position in positions {
+                text[position] = key[digraph];
+            }
+        }
+        let mut current = trigrams.score(&text);
+        for step in 0..options.annealing_steps {
+            let temperature =
+                start_temperature * (1.0 - step as f64 / options.annealing_steps as f64) + 0.01;
+            let (a, b) = (rng.below(cells), rng.below(cells));
+            if a == b {
+                continue;
+            }
+            let swap = |key: &mut Vec<char>, text: &mut Vec<char>| {
+                key.swap(a, b);
+                for &position in occurrences[a].iter().chain(&occurrences[b]) {
+                    text[position] = key[digraphs[position]];
+                }
+            };
+            swap(&mut key, &mut text);
+            let candidate = trigrams.score(&text);
+            let delta = candidate - current;
+            if delta >= 0.0 || rng.unit() < (delta / temperature).exp() {
+                current = candidate;
+                if current > best_score {
+                    best_score = current;
+                    best_key.clone_from(&key);
+                }
+            } else {
+                swap(&mut key, &mut text);
+            }
+            if (step + 1) % report_every == 0 {
+                progress(&Progress {
+                    stage: Stage::Substitution,
+                    step: restart * options.annealing_steps + step + 1,
+                    steps,
+                    best_score,
+                });
+            }
+        }
+    }
+    let cells: String = best_key.into_iter().collect();
+    let square = PolybiusSquare::new(&cells, variant)
+        .expect("the key is a permutation of the charset of the variant");
+    (square, best_score)
+}
+
+/// Recovers the column key and the square of `ciphertext` without knowing either. Returns the
+/// candidates for the best column keys found, most likely first, calling `progress` as the
+/// search goes on.
+pub fn break_adfgvx(
+    ciphertext: &str,
+    variant: Variant,
+    options: &AttackOptions,
+    mut progress: impl FnMut(&Progress),
+) -> Result<Vec<Candidate>, CipherError> {
+    let columns = search_columns(ciphertext, variant, options, &mut progress)?;
+    let ciphertext = clean(ciphertext, variant)?;
+    let mut candidates: Vec<Candidate> = columns
+        .into_iter()
+        .take(options.column_candidates)
+        .map(|(column_key, _)| {
+            let digraphs = digraphs(&ciphertext, &column_key, variant);
+            let (square, score) = solve_substitution(&digraphs, variant, options, &mut progress);
+            let size = variant.labels().len();
+            let plaintext: String = digraphs
+                .iter()
+                .map(|&digraph| square.get(digraph / size, digraph % size))
+                .collect();
+            Candidate {
+                column_key,
+                square,
+                score: score / digraphs.len().saturating_sub(2).max(1) as f64,
+                plaintext,
+            }
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encrypt_with_square, Padding};
+
+    const SAMPLE: &str = "Four score and seven years ago our fathers brought forth on this \
+        continent a new nation conceived in liberty and dedicated to the proposition that all \
+        men are created equal Now we are engaged in a great civil war testing whether that \
+        nation or any nation so conceived and so dedicated can long endure We are met on a \
+        great battlefield of that war We have come to dedicate a portion of that field as a \
+        final resting place for those who here gave their lives that that nation might live It \
+        is altogether fitting and proper that we should do this";
+
+    fn options() -> AttackOptions {
+        AttackOptions {
+            min_columns: 4,
+            max_columns: 7,
+            column_restarts: 6,
+            column_candidates: 1,
+            annealing_restarts: 2,
+            annealing_steps: 8_000,
+            seed: 1,
+        }
+    }
+
+    #[test]
+    fn test_search_columns() {
+        let square = PolybiusSquare::random(11, Variant::Adfgvx);
+        let column_key = [4, 1, 6, 2, 5, 3];
+        let ciphertext = encrypt_with_square(SAMPLE, &square, &column_key, Padding::None).unwrap();
+        let columns =
+            search_columns(&ciphertext, Variant::Adfgvx, &options(), &mut |_| {}).unwrap();
+        // Swapping the columns of every digraph gives the transposed square, which is as good.
+        let transposed = [1, 4, 2, 6, 3, 5];
+        assert!(columns[0].0 == column_key || columns[0].0 == transposed);
+        let chars: Vec<char> = ciphertext.chars().collect();
+        let score =
+            |key: &[u8]| column_score(&digraphs(&chars, key, Variant::Adfgvx), Variant::Adfgvx);
+        assert!((score(&column_key) - score(&transposed)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_climb_wide_keys() {
+        let square = PolybiusSquare::random(12, Variant::Adfgvx);
+        let column_key = [7, 2, 9, 4, 1, 8, 3, 6, 5];
+        let ciphertext = encrypt_with_square(SAMPLE, &square, &column_key, Padding::None).unwrap();
+        let options = AttackOptions {
+            min_columns: 9,
+            max_columns: 9,
+            column_restarts: 30,
+            ..options()
+        };
+        let columns = search_columns(&ciphertext, Variant::Adfgvx, &options, &mut |_| {}).unwrap();
+        assert_eq!(column_key.to_vec(), columns[0].0);
+    }
+
+    #[test]
+    fn test_break_adfgvx() {
+        let square = PolybiusSquare::keyed("privacy", Variant::Adfgvx);
+        let column_key = [3, 5, 1, 4, 2];
+        let ciphertext = encrypt_with_square(SAMPLE, &square, &column_key, Padding::None).unwrap();
+        let mut reports = Vec::new();
+        let candidates = break_adfgvx(&ciphertext, Variant::Adfgvx, &options(), |progress| {
+            reports.push(progress.clone())
+        })
+        .unwrap();
+        let best = &candidates[0];
+        assert_eq!(column_key.to_vec(), best.column_key);
+        assert_eq!(Variant::Adfgvx, best.square.variant());
+        let expected: String = SAMPLE
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        // Letters that never occur cannot be placed, and rare ones may be swapped around.
+        let correct = expected
+            .chars()
+            .zip(best.plaintext.chars())
+            .filter(|(a, b)| a == b)
+            .count();
+        assert!(correct * 10 >= expected.len() * 9, "{}", best.plaintext);
+        assert!(reports.iter().any(|report| report.stage == Stage::Columns));
+        assert!(reports
+            .iter()
+            .any(|report| report.stage == Stage::Substitution));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let options = AttackOptions::default();
+        assert!(break_adfgvx("ADFGVXQ", Variant::Adfgvx, &options, |_| {}).is_err());
+        assert!(break_adfgvx("ADF", Variant::Adfgvx, &options, |_| {}).is_err());
+        assert!(break_adfgvx("ADFGVX", Variant::Adfgx, &options, |_| {}).is_err());
+    }
+}
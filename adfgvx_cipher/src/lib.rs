@@ -1,108 +1,625 @@
-use std::collections::HashMap;
-
+use utils::cipher::{
+    key_value, parse_permutation, validate_keys, Cipher, CipherMode, KeyField, KeyKind, Keys,
+    ALPHANUMERIC,
+};
+use utils::error::CipherError;
+use utils::stream::StreamCipher;
 use utils::*;
 
-pub fn encrypt_adfgvx(plaintext: String, keyword: String, column_key: Vec<u8>) -> String {
-    let adfgvx = ['A', 'D', 'F', 'G', 'V', 'X'];
-    let polybius_square = polybius_square(keyword);
-    let mut intermediate = String::new();
-    for letter in plaintext.chars() {
+pub mod analysis;
+pub mod square;
+pub mod substitution;
+
+use square::PolybiusSquare;
+use substitution::Substitution;
+
+/// Which of the two historical squares to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    /// 6x6 square over A-Z and 0-9.
+    #[default]
+    Adfgvx,
+    /// 5x5 square over A-Z with I and J sharing a cell.
+    Adfgx,
+}
+
+impl Variant {
+    /// The names accepted by [`Variant::from_name`], the default first.
+    pub const NAMES: [&'static str; 2] = ["adfgvx", "adfgx"];
+
+    pub fn from_name(name: &str) -> Option<Variant> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "adfgvx" => Some(Variant::Adfgvx),
+            "adfgx" => Some(Variant::Adfgx),
+            _ => None,
+        }
+    }
+
+    /// The letters the rows and columns of the square are labelled with.
+    pub fn labels(&self) -> &'static [char] {
+        match self {
+            Variant::Adfgvx => &['A', 'D', 'F', 'G', 'V', 'X'],
+            Variant::Adfgx => &['A', 'D', 'F', 'G', 'X'],
+        }
+    }
+
+    /// The characters the square is filled with, in the order used after the keyword.
+    pub fn charset(&self) -> &'static str {
+        match self {
+            Variant::Adfgvx => ALPHANUMERIC,
+            Variant::Adfgx => "ABCDEFGHIKLMNOPQRSTUVWXYZ",
+        }
+    }
+
+    /// Upper cases `c` and folds J into I for the 5x5 square.
+    pub(crate) fn normalize(&self, c: char) -> char {
+        match (self, c.to_ascii_uppercase()) {
+            (Variant::Adfgx, 'J') => 'I',
+            (_, c) => c,
+        }
+    }
+}
+
+/// How the last row of the transposition block is completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Padding {
+    /// Leave the last row unfilled, so that the columns on its right are one character short.
+    #[default]
+    None,
+    /// Append the given null symbol to the plaintext until the last row is full. Decryption
+    /// cannot tell nulls from a message that ends in the same symbol, so such messages are
+    /// rejected; [`Padding::LengthPrefixed`] has no such restriction.
+    Null(char),
+    /// Append filler until the last row is full and start the plaintext with one character of
+    /// the square whose position in [`Variant::charset`] is the number of fillers.
+    LengthPrefixed,
+}
+
+impl Padding {
+    /// The names accepted by [`Padding::from_name`], the default first.
+    pub const NAMES: [&'static str; 3] = ["none", "null", "length-prefixed"];
+
+    /// The filler appended by [`Padding::LengthPrefixed`]. Which one does not matter, as the
+    /// prefix says how many there are.
+    pub const FILLER: char = 'X';
+
+    /// Looks a padding policy up by name, using `null` as the null symbol.
+    pub fn from_name(name: &str, null: char) -> Option<Padding> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "none" | "irregular" => Some(Padding::None),
+            "null" => Some(Padding::Null(null)),
+            "length-prefixed" | "length" => Some(Padding::LengthPrefixed),
+            _ => None,
+        }
+    }
+
+    /// Adds the padding to `plaintext`, a run of characters from the square, so that its
+    /// substitution fills a whole number of rows of `width` columns.
+    fn pad(
+        &self,
+        mut plaintext: Vec<char>,
+        width: usize,
+        variant: Variant,
+    ) -> Result<Vec<char>, CipherError> {
+        let charset: Vec<char> = variant.charset().chars().collect();
+        let prefix = usize::from(*self == Padding::LengthPrefixed);
+        // Each character becomes two, so count how many more are needed to reach a full row.
+        let fillers = (0..width)
+            .find(|fillers| (2 * (plaintext.len() + prefix + fillers)).is_multiple_of(width))
+            .unwrap_or_default();
+        match *self {
+            Padding::None => {}
+            Padding::Null(null) => {
+                let null = variant.normalize(null);
+                if !charset.contains(&null) {
+                    return Err(CipherError::InvalidKey(format!(
+                        "the null symbol {null:?} is not in the square"
+                    )));
+                }
+                if plaintext.last() == Some(&null) {
+                    return Err(CipherError::InvalidKey(format!(
+                        "the message ends in the null symbol {null:?}, pick another one or use \
+                         length-prefixed padding"
+                    )));
+                }
+                plaintext.extend(std::iter::repeat_n(null, fillers));
+            }
+            Padding::LengthPrefixed => {
+                let prefix = *charset.get(fillers).ok_or_else(|| {
+                    CipherError::InvalidKey(format!(
+                        "the column key is too long to prefix {fillers} fillers"
+                    ))
+                })?;
+                plaintext.insert(0, prefix);
+                plaintext.extend(std::iter::repeat_n(Padding::FILLER, fillers));
+            }
+        }
+        Ok(plaintext)
+    }
+
+    /// Removes the padding added by [`Padding::pad`] for `width` columns from a decrypted
+    /// `plaintext`.
+    fn strip(
+        &self,
+        plaintext: String,
+        width: usize,
+        variant: Variant,
+    ) -> Result<String, CipherError> {
+        match *self {
+            Padding::None => Ok(plaintext),
+            Padding::Null(null) => {
+                // Rows fill up again every `width / 2` characters when the width is even, so no
+                // more nulls than that can have been added.
+                let most = if width.is_multiple_of(2) {
+                    width / 2
+                } else {
+                    width
+                } - 1;
+                let null = variant.normalize(null);
+                let mut chars: Vec<char> = plaintext.chars().collect();
+                let nulls = chars.iter().rev().take(most).take_while(|&&c| c == null);
+                chars.truncate(chars.len() - nulls.count());
+                Ok(chars.into_iter().collect())
+            }
+            Padding::LengthPrefixed => {
+                let chars: Vec<char> = plaintext.chars().collect();
+                let fillers = chars
+                    .first()
+                    .and_then(|prefix| variant.charset().chars().position(|c| c == *prefix))
+                    .filter(|fillers| *fillers < chars.len())
+                    .ok_or_else(|| CipherError::MalformedCiphertext {
+                        position: 0,
+                        reason: "the length prefix does not fit the message".to_string(),
+                    })?;
+                Ok(chars[1..chars.len() - fillers].iter().collect())
+            }
+        }
+    }
+}
+
+/// Checks that the keyword only uses characters from the square.
+fn validate_keyword(keyword: &str, variant: Variant) -> Result<(), CipherError> {
+    match keyword
+        .chars()
+        .find(|&c| !variant.charset().contains(variant.normalize(c)))
+    {
+        Some(c) if variant == Variant::Adfgx => {
+            Err(CipherError::InvalidKey(format!("{c:?} is not a letter")))
+        }
+        Some(c) => Err(CipherError::InvalidKey(format!(
+            "{c:?} is not a letter or a digit"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// The characters of `text` the cipher reads, leaving out whitespace and [`PUNCTUATION`], with
+/// the offset of each in `text`. The offset of the end of `text` comes last.
+fn significant(text: &str) -> (String, Vec<usize>) {
+    let mut offsets = Vec::new();
+    let mut significant = String::new();
+    let mut length = 0;
+    for (offset, c) in text.chars().enumerate() {
+        length = offset + 1;
+        if !c.is_whitespace() && !PUNCTUATION.contains(c) {
+            offsets.push(offset);
+            significant.push(c);
+        }
+    }
+    offsets.push(length);
+    (significant, offsets)
+}
+
+/// Moves the position of `error` from the text [`significant`] returned back to the text it was
+/// taken from.
+fn at_offset(error: CipherError, offsets: &[usize]) -> CipherError {
+    let offset = |position: usize| offsets[position.min(offsets.len() - 1)];
+    match error {
+        CipherError::UnsupportedCharacter {
+            character,
+            position,
+        } => CipherError::UnsupportedCharacter {
+            character,
+            position: offset(position),
+        },
+        CipherError::MalformedCiphertext { position, reason } => CipherError::MalformedCiphertext {
+            position: offset(position),
+            reason,
+        },
+        error => error,
+    }
+}
+
+/// Checks that the column key is a permutation of the numbers 1 to n, n being its length.
+fn validate_column_key(column_key: &[u8]) -> Result<(), CipherError> {
+    if column_key.is_empty() {
+        return Err(CipherError::InvalidKey(
+            "the column key needs at least 1 number".to_string(),
+        ));
+    }
+    for i in 1..=column_key.len() {
+        if !column_key.iter().any(|&key| key as usize == i) {
+            return Err(CipherError::InvalidKey(format!(
+                "the column key is missing the number {i}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub fn encrypt_adfgvx(
+    plaintext: String,
+    keyword: String,
+    column_key: Vec<u8>,
+) -> Result<String, CipherError> {
+    encrypt_variant(
+        &plaintext,
+        &keyword,
+        &column_key,
+        Variant::Adfgvx,
+        Padding::None,
+    )
+}
+
+/// Substitutes every character of `plaintext` with the labels of its row and column in the
+/// square keyed with `keyword`, then transposes the result with `column_key`, completing the
+/// last row as `padding` says.
+pub fn encrypt_variant(
+    plaintext: &str,
+    keyword: &str,
+    column_key: &[u8],
+    variant: Variant,
+    padding: Padding,
+) -> Result<String, CipherError> {
+    validate_keyword(keyword, variant)?;
+    let square = PolybiusSquare::keyed(keyword, variant);
+    encrypt_with_square(plaintext, &square, column_key, padding)
+}
+
+/// Like [`encrypt_variant`], but with a square that may be laid out in any order.
+pub fn encrypt_with_square(
+    plaintext: &str,
+    square: &PolybiusSquare,
+    column_key: &[u8],
+    padding: Padding,
+) -> Result<String, CipherError> {
+    validate_column_key(column_key)?;
+    let variant = square.variant();
+    let labels = variant.labels();
+    let charset = variant.charset();
+    let mut letters = Vec::new();
+    for (position, letter) in plaintext.chars().enumerate() {
         if letter.is_whitespace() {
             continue;
         }
-        let (col, row) = find_char(&polybius_square, letter.to_ascii_uppercase()).unwrap();
-        intermediate.push_str(&format!("{}{}", adfgvx[row], adfgvx[col]));
+        let normalized = variant.normalize(letter);
+        if !charset.contains(normalized) {
+            return Err(CipherError::UnsupportedCharacter {
+                character: letter,
+                position,
+            });
+        }
+        letters.push(normalized);
+    }
+    let mut intermediate = String::new();
+    for letter in padding.pad(letters, column_key.len(), variant)? {
+        let (row, col) = square.position(letter).unwrap();
+        intermediate.push(labels[row]);
+        intermediate.push(labels[col]);
     }
-    intermediate = remove_whitespace(&mut intermediate);
+    Ok(transpose_columns(&intermediate, column_key))
+}
+
+/// Writes `intermediate` row by row under the column key and reads it back column by column in
+/// the order given by the key. The last row is left unfilled, so the columns on its right end
+/// up one character shorter.
+fn transpose_columns(intermediate: &str, column_key: &[u8]) -> String {
     let slice = &intermediate.chars().collect::<Vec<_>>()[..];
-    let mut chunked = slice
-        .chunks(6)
-        .map(|chunk| chunk.to_vec())
-        .collect::<Vec<_>>();
-    if chunked.last().unwrap().len() != 6 {
-        for _ in 0..(6 - chunked.last().unwrap().len()) {
-            let len = chunked.len() - 1;
-            chunked[len].push('X');
-            chunked[len].push('X');
-        }
-    }
-    //let slice2 = &chunked.into_iter().flatten().collect::<Vec<char>>()[..];
-    //let mut chunked = slice2.chunks(len_chunked).map(|chunk|chunk.to_vec()).collect::<Vec<_>>();
-    let transposed_matrix = transpose(chunked.clone());
-    let mut order: Vec<u8> = Vec::new();
-    for i in 0..6 {
-        let index = column_key.iter().position(|&r| r == i + 1).unwrap();
-        order.push(index.try_into().unwrap());
-    }
-    let mut ciphertext: String = String::new();
-    for i in order {
-        let string = transposed_matrix[i as usize].iter().collect::<String>();
-        ciphertext.push_str(&string);
+    let width = column_key.len();
+    let mut ciphertext = String::with_capacity(slice.len());
+    for column in column_order(column_key) {
+        ciphertext.extend(slice.iter().skip(column).step_by(width));
     }
     ciphertext
 }
 
+/// Reverses [`transpose_columns`], working out from the length of `slice` which columns were
+/// one character short.
+fn untranspose_columns(slice: &[char], column_key: &[u8]) -> Vec<char> {
+    let width = column_key.len();
+    let (rows, full_columns) = (slice.len() / width, slice.len() % width);
+    let mut columns = vec![&slice[..0]; width];
+    let mut start = 0;
+    for column in column_order(column_key) {
+        let height = rows + usize::from(column < full_columns);
+        columns[column] = &slice[start..start + height];
+        start += height;
+    }
+    let mut intermediate = Vec::with_capacity(slice.len());
+    for row in 0..=rows {
+        intermediate.extend(columns.iter().filter_map(|column| column.get(row)));
+    }
+    intermediate
+}
+
+/// The indices of the columns in the order they are read out: first the column numbered 1 in
+/// the key, then the one numbered 2 and so on.
+fn column_order(column_key: &[u8]) -> Vec<usize> {
+    (1..=column_key.len())
+        .filter_map(|rank| column_key.iter().position(|&key| key as usize == rank))
+        .collect()
+}
+
 pub fn polybius_square(keyword: String) -> Vec<Vec<char>> {
-    let non_repeating_string = remove_repeating_letters(keyword).to_ascii_uppercase();
-    let mut square: Vec<Vec<char>> = Vec::new();
-    let charset = remove_charset(
-        non_repeating_string.chars().collect(),
-        String::from("ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"),
-    );
-    let combined = format!("{non_repeating_string}{charset}");
-    let charset = &combined.chars().collect::<Vec<_>>()[..];
-    let mut charset = charset.chunks(6).map(|chunk| chunk.to_vec()).collect();
-    square.append(&mut charset);
-
-    square
-}
-
-pub fn decrypt_adfgvx(encrypted_text: String, key: String, column_key: Vec<u8>) -> String {
-    let length = encrypted_text.len();
+    keyed_square(&keyword, Variant::Adfgvx)
+}
+
+/// The square of `variant` filled with the distinct characters of `keyword` followed by the
+/// rest of [`Variant::charset`], as rows.
+pub fn keyed_square(keyword: &str, variant: Variant) -> Vec<Vec<char>> {
+    PolybiusSquare::keyed(keyword, variant).rows()
+}
+
+pub fn decrypt_adfgvx(
+    encrypted_text: String,
+    key: String,
+    column_key: Vec<u8>,
+) -> Result<String, CipherError> {
+    decrypt_variant(
+        &encrypted_text,
+        &key,
+        &column_key,
+        Variant::Adfgvx,
+        Padding::None,
+    )
+}
+
+/// Reverses [`encrypt_variant`], removing the padding again.
+pub fn decrypt_variant(
+    encrypted_text: &str,
+    key: &str,
+    column_key: &[u8],
+    variant: Variant,
+    padding: Padding,
+) -> Result<String, CipherError> {
+    validate_keyword(key, variant)?;
+    let square = PolybiusSquare::keyed(key, variant);
+    decrypt_with_square(encrypted_text, &square, column_key, padding)
+}
+
+/// Reverses [`encrypt_with_square`].
+pub fn decrypt_with_square(
+    encrypted_text: &str,
+    square: &PolybiusSquare,
+    column_key: &[u8],
+    padding: Padding,
+) -> Result<String, CipherError> {
+    validate_column_key(column_key)?;
+    let variant = square.variant();
+    let labels = variant.labels();
     let slice = &encrypted_text.chars().collect::<Vec<char>>()[..];
-    let mut encrypted_text = encrypted_text.clone();
-    if length%6 != 0{
-        for _ in 0..(length%6){
-            encrypted_text.push_str("XX");
+    if let Some(position) = slice.iter().position(|c| !labels.contains(c)) {
+        let names: Vec<String> = labels.iter().map(|c| c.to_string()).collect();
+        return Err(CipherError::MalformedCiphertext {
+            position,
+            reason: format!(
+                "{:?} is not one of {} or {}",
+                slice[position],
+                names[..names.len() - 1].join(", "),
+                names[names.len() - 1]
+            ),
+        });
+    }
+    if !slice.len().is_multiple_of(2) {
+        return Err(CipherError::MalformedCiphertext {
+            position: slice.len(),
+            reason: "the length is not a multiple of 2".to_string(),
+        });
+    }
+    let intermediate = untranspose_columns(slice, column_key);
+    let mut plaintext = String::new();
+    for pair in intermediate.chunks(2) {
+        let row_index = labels.iter().position(|c| *c == pair[0]).unwrap();
+        let column_index = labels.iter().position(|c| *c == pair[1]).unwrap();
+        plaintext.push(square.get(row_index, column_index));
+    }
+    padding.strip(plaintext, column_key.len(), variant)
+}
+
+/// Parses a column key: either the numbers 1 to n in some order, separated by whitespace or
+/// commas, or a keyword such as `"PRIVACY"` whose letters give the order alphabetically.
+pub fn parse_column_key(column_key: &str) -> Result<Vec<u8>, CipherError> {
+    parse_permutation(column_key, None, true)
+        .map_err(|reason| CipherError::InvalidKey(format!("column key: {reason}")))?
+        .into_iter()
+        .map(|number| {
+            u8::try_from(number).map_err(|_| {
+                CipherError::InvalidKey("the column key can be at most 255 long".to_string())
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Adfgvx;
+
+impl Adfgvx {
+    /// The variant picked in `keys`, ADFGVX if none was.
+    pub fn variant(keys: &Keys) -> Variant {
+        Variant::from_name(key_value(keys, "variant")).unwrap_or_default()
+    }
+
+    /// The square given in `keys`: the one in the square field if there is one, read from a
+    /// file for `@path` and shuffled for `seed:N`, the square keyed with the keyword otherwise.
+    pub fn square(keys: &Keys) -> Result<PolybiusSquare, CipherError> {
+        let variant = Self::variant(keys);
+        let square = key_value(keys, "square").trim();
+        if let Some(path) = square.strip_prefix('@') {
+            return PolybiusSquare::from_file(path.trim(), variant);
+        }
+        if let Some(seed) = square.strip_prefix("seed:") {
+            let seed = seed.trim().parse().map_err(|_| {
+                CipherError::InvalidKey(format!("{:?} is not a valid seed", seed.trim()))
+            })?;
+            return Ok(PolybiusSquare::random(seed, variant));
         }
+        if !square.is_empty() {
+            return PolybiusSquare::parse(square, variant);
+        }
+        let keyword = remove_whitespace(&mut remove_punctuation(key_value(keys, "keyword")));
+        validate_keyword(&keyword, variant)?;
+        Ok(PolybiusSquare::keyed(&keyword, variant))
     }
-    let chunked = slice
-        .chunks(length / 6_usize)
-        .map(|x| x.to_vec())
-        .collect::<Vec<_>>();
-    let order: Vec<u8> = column_key.iter().map(|key| (key - 1)).collect();
-    let mut intermediate = String::new();
-    let mut intermediate_vector = Vec::new();
-    for i in order {
-        let chunk = &chunked[i as usize];
-        intermediate_vector.push(chunk.clone());
+
+    /// The padding policy picked in `keys`, with the null symbol given there. Null padding has no
+    /// default symbol, since whichever one is picked rules out messages ending in it.
+    pub fn padding(keys: &Keys) -> Result<Padding, CipherError> {
+        let mut null = key_value(keys, "null")
+            .chars()
+            .filter(|c| !c.is_whitespace());
+        let symbol = null.next();
+        if null.next().is_some() {
+            return Err(CipherError::InvalidKey(
+                "the null symbol must be a single character".to_string(),
+            ));
+        }
+        let padding = Padding::from_name(key_value(keys, "padding"), symbol.unwrap_or_default())
+            .unwrap_or_default();
+        match (padding, symbol) {
+            (Padding::Null(_), None) => Err(CipherError::InvalidKey(
+                "null padding needs a null symbol; length-prefixed padding needs none and works \
+                 for any message"
+                    .to_string(),
+            )),
+            (padding, _) => Ok(padding),
+        }
     }
-    let transposed_matrix = transpose(intermediate_vector);
-    for vector in transposed_matrix {
-        let string = vector.iter().collect::<String>();
-        intermediate.push_str(&string);
+}
+
+impl Cipher for Adfgvx {
+    fn id(&self) -> &'static str {
+        "adfgvx"
+    }
+
+    fn name(&self) -> &'static str {
+        "ADFGVX Cipher"
+    }
+
+    fn description(&self) -> &'static str {
+        "Keyed 6x6 (ADFGVX) or 5x5 (ADFGX) Polybius square followed by a columnar transposition"
+    }
+
+    fn key_schema(&self) -> Vec<KeyField> {
+        vec![
+            KeyField {
+                id: "keyword",
+                label: "Keyword",
+                flag: "key",
+                kind: KeyKind::Text {
+                    charset: ALPHANUMERIC,
+                    min_len: 0,
+                },
+            },
+            KeyField {
+                id: "square",
+                label: "Square (optional: cells row by row, @file or seed:N)",
+                flag: "square",
+                kind: KeyKind::Text {
+                    charset: "",
+                    min_len: 0,
+                },
+            },
+            KeyField {
+                id: "column_key",
+                label: "Column Key",
+                flag: "columns",
+                kind: KeyKind::Permutation {
+                    size: None,
+                    keyword: true,
+                },
+            },
+            KeyField {
+                id: "variant",
+                label: "Variant",
+                flag: "variant",
+                kind: KeyKind::Choice {
+                    options: &Variant::NAMES,
+                },
+            },
+            KeyField {
+                id: "padding",
+                label: "Padding",
+                flag: "padding",
+                kind: KeyKind::Choice {
+                    options: &Padding::NAMES,
+                },
+            },
+            KeyField {
+                id: "null",
+                label: "Null symbol (for null padding)",
+                flag: "null",
+                kind: KeyKind::Text {
+                    charset: ALPHANUMERIC,
+                    min_len: 0,
+                },
+            },
+        ]
+    }
+
+    fn encrypt(&self, plaintext: &str, keys: &Keys) -> Result<String, CipherError> {
+        validate_keys(&self.key_schema(), keys)?;
+        let (letters, offsets) = significant(plaintext);
+        encrypt_with_square(
+            &letters,
+            &Self::square(keys)?,
+            &parse_column_key(key_value(keys, "column_key"))?,
+            Self::padding(keys)?,
+        )
+        .map_err(|e| at_offset(e, &offsets))
+    }
+
+    fn key_material(&self, keys: &Keys) -> Option<String> {
+        let square = Adfgvx::square(keys).ok()?;
+        Some(format!(
+            "{}\nShare this square as {square}\n",
+            square.grid()
+        ))
+    }
+
+    fn decrypt(&self, ciphertext: &str, keys: &Keys) -> Result<String, CipherError> {
+        validate_keys(&self.key_schema(), keys)?;
+        let (labels, offsets) = significant(ciphertext);
+        decrypt_with_square(
+            &labels.to_ascii_uppercase(),
+            &Self::square(keys)?,
+            &parse_column_key(key_value(keys, "column_key"))?,
+            Self::padding(keys)?,
+        )
+        .map_err(|e| at_offset(e, &offsets))
+    }
+
+    /// Only the substitution streams. The transposition reads the columns out of the whole
+    /// message, so it is buffered unless the column key is a single column without padding,
+    /// e.g. `--columns 1` on the command line, which leaves the substituted text as it is.
+    fn stream(
+        &self,
+        mode: CipherMode,
+        keys: &Keys,
+    ) -> Result<Option<Box<dyn StreamCipher>>, CipherError> {
+        validate_keys(&self.key_schema(), keys)?;
+        let column_key = parse_column_key(key_value(keys, "column_key"))?;
+        if column_key.len() > 1 || Self::padding(keys)? != Padding::None {
+            return Ok(None);
+        }
+        let substitution = Substitution::new(Self::square(keys)?, mode).skipping(PUNCTUATION);
+        Ok(Some(Box::new(substitution)))
     }
-    let mut plaintext = String::new();
-    let square = polybius_square(key);
-    let letter_indices =
-        HashMap::from([('A', 0), ('D', 1), ('F', 2), ('G', 3), ('V', 4), ('X', 5)]);
-    let intermediate_sliced = &intermediate.chars().collect::<Vec<_>>()[..];
-    let intermediate_pairs = intermediate_sliced
-        .chunks(2)
-        .map(|x| x.to_vec())
-        .collect::<Vec<_>>();
-    for pair in intermediate_pairs {
-        let row_index = letter_indices.get(&pair[0]).unwrap();
-        let column_index = letter_indices.get(&pair[1]).unwrap();
-        let letter = square[*row_index as usize][*column_index as usize];
-        plaintext.push(letter);
-    }
-    plaintext
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_polybius_square() {
@@ -118,6 +635,358 @@ mod tests {
         let keyword = String::from("aarav");
         let plaintext = String::from("certified loverboy");
         let column_key: Vec<u8> = vec![5, 1, 3, 4, 2, 6];
-        assert_eq!("", encrypt_adfgvx(plaintext, keyword, column_key))
+        assert_eq!(
+            Ok(String::from("VFVDAVADAAADDDFAVAVAVDAAGDFDFDDXFG")),
+            encrypt_adfgvx(plaintext, keyword, column_key)
+        )
+    }
+
+    #[test]
+    fn test_unsupported_character() {
+        assert_eq!(
+            Err(CipherError::UnsupportedCharacter {
+                character: '!',
+                position: 5
+            }),
+            encrypt_adfgvx(
+                String::from("hello!"),
+                String::from("aarav"),
+                vec![5, 1, 3, 4, 2, 6]
+            )
+        );
+        // Through the cipher the position is still that of the caller's input, counting the
+        // whitespace and punctuation that are left out.
+        let keys: Keys = [("keyword", "aarav"), ("column_key", "privacy")]
+            .into_iter()
+            .map(|(id, value)| (id.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(
+            Err(CipherError::UnsupportedCharacter {
+                character: '!',
+                position: 9
+            }),
+            Adfgvx.encrypt("Hello, wo!", &keys)
+        );
+        assert!(matches!(
+            Adfgvx.decrypt("AD, FG VX.Q", &keys),
+            Err(CipherError::MalformedCiphertext { position: 10, .. })
+        ));
+        assert!(matches!(
+            Adfgvx.decrypt("AD FG V ", &keys),
+            Err(CipherError::MalformedCiphertext { position: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn test_adfgx() {
+        let column_key = [5, 1, 3, 4, 2, 6];
+        let ciphertext = encrypt_variant(
+            "jump over the lazy dog",
+            "kryptos",
+            &column_key,
+            Variant::Adfgx,
+            Padding::None,
+        )
+        .unwrap();
+        assert!(ciphertext.chars().all(|c| "ADFGX".contains(c)));
+        assert_eq!(
+            Ok(String::from("IUMPOVERTHELAZYDOG")),
+            decrypt_variant(
+                &ciphertext,
+                "kryptos",
+                &column_key,
+                Variant::Adfgx,
+                Padding::None
+            )
+        );
+        assert_eq!(5, keyed_square("kryptos", Variant::Adfgx).len());
+        assert!(encrypt_variant(
+            "route 66",
+            "kryptos",
+            &column_key,
+            Variant::Adfgx,
+            Padding::None
+        )
+        .is_err());
+        assert!(encrypt_variant(
+            "route",
+            "kryptos9",
+            &column_key,
+            Variant::Adfgx,
+            Padding::None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_irregular_columns() {
+        let plaintext = "attack at 1200am";
+        for column_key in [
+            vec![1],
+            vec![2, 1],
+            vec![3, 1, 4, 2, 5],
+            (1..=40).rev().collect(),
+        ] {
+            let ciphertext = encrypt_variant(
+                plaintext,
+                "privacy",
+                &column_key,
+                Variant::Adfgvx,
+                Padding::None,
+            )
+            .unwrap();
+            assert_eq!(28, ciphertext.len());
+            assert_eq!(
+                Ok(String::from("ATTACKAT1200AM")),
+                decrypt_variant(
+                    &ciphertext,
+                    "privacy",
+                    &column_key,
+                    Variant::Adfgvx,
+                    Padding::None
+                )
+            );
+        }
+        // Columns of height 3, 3 and 2, read out in the order 2, 1, 3.
+        assert_eq!("DVDAGAFX", transpose_columns("ADFGVXAD", &[2, 1, 3]));
+        assert_eq!(
+            "ADFGVXAD".chars().collect::<Vec<_>>(),
+            untranspose_columns(&"DVDAGAFX".chars().collect::<Vec<_>>(), &[2, 1, 3])
+        );
+        assert!(
+            decrypt_variant("ADF", "privacy", &[2, 1], Variant::Adfgvx, Padding::None).is_err()
+        );
+    }
+
+    #[test]
+    fn test_column_keyword() {
+        assert_eq!(Ok(vec![4, 5, 3, 6, 1, 2, 7]), parse_column_key("privacy"));
+        assert_eq!(Ok(vec![5, 1, 3, 4, 2, 6]), parse_column_key("5 1 3 4 2 6"));
+        assert!(parse_column_key("5 1 3 4 2 2").is_err());
+        assert!(parse_column_key("5 1 3 4 6").is_err());
+
+        let cipher = Adfgvx;
+        let keys: Keys = [
+            ("keyword".to_string(), "aarav".to_string()),
+            ("column_key".to_string(), "privacy".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let ciphertext = cipher.encrypt("attack at 1200am", &keys).unwrap();
+        assert_eq!(
+            encrypt_adfgvx(
+                "attack at 1200am".to_string(),
+                "aarav".to_string(),
+                vec![4, 5, 3, 6, 1, 2, 7]
+            ),
+            Ok(ciphertext.clone())
+        );
+        assert_eq!(
+            Ok("ATTACKAT1200AM".to_string()),
+            cipher.decrypt(&ciphertext, &keys)
+        );
+    }
+
+    #[test]
+    fn test_padding() {
+        let column_key = [3, 1, 4, 2, 5];
+        let encrypt = |plaintext, padding| {
+            encrypt_variant(plaintext, "aarav", &column_key, Variant::Adfgvx, padding).unwrap()
+        };
+        let decrypt = |ciphertext: &str, padding| {
+            decrypt_variant(ciphertext, "aarav", &column_key, Variant::Adfgvx, padding)
+        };
+
+        // 7 characters need 3 nulls to fill 4 rows of 5.
+        let ciphertext = encrypt("attack x", Padding::Null('Q'));
+        assert_eq!(20, ciphertext.len());
+        assert_eq!(
+            Ok("ATTACKX".to_string()),
+            decrypt(&ciphertext, Padding::Null('q'))
+        );
+        assert!(encrypt_variant(
+            "attack q",
+            "aarav",
+            &column_key,
+            Variant::Adfgvx,
+            Padding::Null('Q')
+        )
+        .is_err());
+
+        // The prefix says 2 fillers follow the 7 characters.
+        let ciphertext = encrypt("attack x", Padding::LengthPrefixed);
+        assert_eq!(20, ciphertext.len());
+        assert_eq!(
+            Ok("CATTACKXXX".to_string()),
+            decrypt(&ciphertext, Padding::None)
+        );
+        assert_eq!(
+            Ok("ATTACKX".to_string()),
+            decrypt(&ciphertext, Padding::LengthPrefixed)
+        );
+        assert!(decrypt("", Padding::LengthPrefixed).is_err());
+
+        let cipher = Adfgvx;
+        let keys: Keys = [
+            ("keyword", "aarav"),
+            ("column_key", "3 1 4 2 5"),
+            ("padding", "null"),
+            ("null", "q"),
+        ]
+        .into_iter()
+        .map(|(id, value)| (id.to_string(), value.to_string()))
+        .collect();
+        let ciphertext = cipher.encrypt("attack x", &keys).unwrap();
+        assert_eq!(encrypt("attack x", Padding::Null('Q')), ciphertext);
+        assert_eq!(
+            Ok("ATTACKX".to_string()),
+            cipher.decrypt(&ciphertext, &keys)
+        );
+        let mut keys = keys;
+        keys.remove("null");
+        assert!(cipher.encrypt("attack x", &keys).is_err());
+
+        // Messages ending in X need no care with length-prefixed padding.
+        keys.insert("padding".to_string(), "length-prefixed".to_string());
+        for plaintext in ["FAX", "ATTACKATSIX", "XXXXX"] {
+            let ciphertext = cipher.encrypt(plaintext, &keys).unwrap();
+            assert_eq!(
+                Ok(plaintext.to_string()),
+                cipher.decrypt(&ciphertext, &keys)
+            );
+        }
+
+        // Only as many nulls as the padding can have added are stripped.
+        let ciphertext = encrypt_variant(
+            "AQQQQQQQQ",
+            "aarav",
+            &[1, 2],
+            Variant::Adfgvx,
+            Padding::None,
+        );
+        assert_eq!(
+            Ok("AQQQQQQQQ".to_string()),
+            decrypt_variant(
+                &ciphertext.unwrap(),
+                "aarav",
+                &[1, 2],
+                Variant::Adfgvx,
+                Padding::Null('Q')
+            )
+        );
+    }
+
+    fn padding() -> impl Strategy<Value = Padding> {
+        prop_oneof![
+            Just(Padding::None),
+            Just(Padding::LengthPrefixed),
+            "[A-Z]".prop_map(|null| Padding::Null(null.chars().next().unwrap())),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(
+            plaintext in "[A-Z0-9]{0,60}",
+            keyword in "[A-Z0-9]{0,10}",
+            column_key in (1..=12_u8).prop_flat_map(|width| Just((1..=width).collect::<Vec<_>>()).prop_shuffle()),
+            padding in padding(),
+            adfgx in any::<bool>(),
+        ) {
+            let variant = if adfgx { Variant::Adfgx } else { Variant::Adfgvx };
+            let plaintext: String = plaintext.chars().filter(|c| variant.charset().contains(*c)).collect();
+            let keyword: String = keyword.chars().filter(|c| variant.charset().contains(*c)).collect();
+            if let Padding::Null(null) = padding {
+                prop_assume!(!plaintext.ends_with(variant.normalize(null)));
+            }
+            let ciphertext = encrypt_variant(&plaintext, &keyword, &column_key, variant, padding).unwrap();
+            if padding != Padding::None {
+                prop_assert!(ciphertext.len().is_multiple_of(column_key.len()));
+            }
+            prop_assert_eq!(
+                Ok(plaintext),
+                decrypt_variant(&ciphertext, &keyword, &column_key, variant, padding)
+            );
+        }
+    }
+
+    #[test]
+    fn test_square_key() {
+        let cipher = Adfgvx;
+        let mut keys: Keys = [("column_key", "privacy"), ("square", "seed:1918")]
+            .into_iter()
+            .map(|(id, value)| (id.to_string(), value.to_string()))
+            .collect();
+        let square = PolybiusSquare::random(1918, Variant::Adfgvx);
+        let ciphertext = cipher.encrypt("attack at dawn", &keys).unwrap();
+        assert_eq!(
+            encrypt_with_square(
+                "attackatdawn",
+                &square,
+                &[4, 5, 3, 6, 1, 2, 7],
+                Padding::None
+            ),
+            Ok(ciphertext.clone())
+        );
+        let material = cipher.key_material(&keys).unwrap();
+        assert!(material.starts_with(&square.grid()));
+        assert!(material.contains(&format!("Share this square as {square}")));
+        keys.insert("square".to_string(), square.grid());
+        assert_eq!(
+            Ok("ATTACKATDAWN".to_string()),
+            cipher.decrypt(&ciphertext, &keys)
+        );
+        keys.insert("square".to_string(), "seed:x".to_string());
+        assert!(cipher.encrypt("attack at dawn", &keys).is_err());
+        assert_eq!(None, cipher.key_material(&keys));
+    }
+
+    #[test]
+    fn test_stream() {
+        let cipher = Adfgvx;
+        let mut keys: Keys = [("keyword", "privacy"), ("column_key", "1")]
+            .into_iter()
+            .map(|(id, value)| (id.to_string(), value.to_string()))
+            .collect();
+        let plaintext = "Attack at 1200am; «go», don't wait.";
+        let ciphertext = cipher.encrypt(plaintext, &keys).unwrap();
+        let run = |mode, text: &str| {
+            let mut stream = cipher.stream(mode, &keys).unwrap().unwrap();
+            let mut output = String::new();
+            for c in text.chars() {
+                stream.update(c.encode_utf8(&mut [0; 4]), &mut output)?;
+            }
+            stream.finish(&mut output).map(|()| output)
+        };
+        assert_eq!(Ok(ciphertext.clone()), run(CipherMode::Encrypt, plaintext));
+        assert_eq!(
+            cipher.decrypt(&ciphertext, &keys),
+            run(CipherMode::Decrypt, &ciphertext)
+        );
+        let lowercase = ciphertext.to_lowercase();
+        assert!(cipher.decrypt(&lowercase, &keys).is_ok());
+        assert_eq!(
+            cipher.decrypt(&lowercase, &keys),
+            run(CipherMode::Decrypt, &lowercase)
+        );
+
+        // The transposition and padding need the whole message.
+        keys.insert("column_key".to_string(), "privacy".to_string());
+        assert!(cipher.stream(CipherMode::Encrypt, &keys).unwrap().is_none());
+        keys.insert("column_key".to_string(), "1".to_string());
+        keys.insert("padding".to_string(), "length-prefixed".to_string());
+        assert!(cipher.stream(CipherMode::Encrypt, &keys).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_column_key() {
+        assert!(matches!(
+            decrypt_adfgvx(
+                String::from("ADFGVX"),
+                String::from("aarav"),
+                vec![1, 1, 3, 4, 2, 6]
+            ),
+            Err(CipherError::InvalidKey(_))
+        ))
     }
 }
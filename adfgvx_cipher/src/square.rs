@@ -0,0 +1,232 @@
+//! The Polybius square the substitution stage looks characters up in.
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use utils::error::CipherError;
+use utils::{remove_charset, remove_repeating_letters};
+
+use crate::Variant;
+
+/// A complete Polybius square of a [`Variant`]: every character of [`Variant::charset`] exactly
+/// once, stored row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolybiusSquare {
+    variant: Variant,
+    cells: Vec<char>,
+}
+
+impl PolybiusSquare {
+    /// Builds a square from its cells written row by row, ignoring whitespace and case. For
+    /// ADFGX a J counts as I.
+    pub fn new(cells: &str, variant: Variant) -> Result<Self, CipherError> {
+        let charset = variant.charset();
+        let cells: Vec<char> = cells
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| variant.normalize(c))
+            .collect();
+        for (i, c) in cells.iter().enumerate() {
+            if !charset.contains(*c) {
+                return Err(CipherError::InvalidKey(format!(
+                    "{c:?} is not allowed in the square"
+                )));
+            }
+            if cells[..i].contains(c) {
+                return Err(CipherError::InvalidKey(format!(
+                    "{c:?} appears more than once in the square"
+                )));
+            }
+        }
+        if let Some(missing) = charset.chars().find(|c| !cells.contains(c)) {
+            return Err(CipherError::InvalidKey(format!(
+                "the square needs {} characters, {missing:?} is missing",
+                charset.len()
+            )));
+        }
+        Ok(PolybiusSquare { variant, cells })
+    }
+
+    /// The classic layout: the distinct characters of `keyword` followed by the rest of
+    /// [`Variant::charset`] in order.
+    pub fn keyed(keyword: &str, variant: Variant) -> Self {
+        let keyword: String = keyword.chars().map(|c| variant.normalize(c)).collect();
+        let non_repeating_string = remove_repeating_letters(keyword);
+        let rest = remove_charset(
+            non_repeating_string.chars().collect(),
+            String::from(variant.charset()),
+        );
+        PolybiusSquare {
+            variant,
+            cells: format!("{non_repeating_string}{rest}").chars().collect(),
+        }
+    }
+
+    /// A shuffled square. The same seed always gives the same square, so sharing the seed is
+    /// enough to share the square.
+    pub fn random(seed: u64, variant: Variant) -> Self {
+        let mut state = seed;
+        let mut cells: Vec<char> = variant.charset().chars().collect();
+        for i in (1..cells.len()).rev() {
+            let j = (split_mix(&mut state) % (i as u64 + 1)) as usize;
+            cells.swap(i, j);
+        }
+        PolybiusSquare { variant, cells }
+    }
+
+    /// Reads a square written either as its cells row by row or as the labelled grid produced
+    /// by [`PolybiusSquare::grid`].
+    pub fn parse(text: &str, variant: Variant) -> Result<Self, CipherError> {
+        if !text.contains('|') {
+            return PolybiusSquare::new(text, variant);
+        }
+        // In a grid every `|` is preceded by a row label and followed by the cells of that row,
+        // so drop the header and the label in front of the next `|`.
+        let segments: Vec<&str> = text.split('|').skip(1).collect();
+        let mut cells = String::new();
+        for (i, segment) in segments.iter().enumerate() {
+            let mut row: Vec<char> = segment.chars().filter(|c| !c.is_whitespace()).collect();
+            if i + 1 < segments.len() {
+                row.pop();
+            }
+            cells.extend(row);
+        }
+        PolybiusSquare::new(&cells, variant)
+    }
+
+    /// Reads a square from a text file in one of the formats [`PolybiusSquare::parse`] accepts.
+    pub fn from_file(path: impl AsRef<Path>, variant: Variant) -> Result<Self, CipherError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| {
+            CipherError::InvalidKey(format!("could not read {}: {e}", path.display()))
+        })?;
+        PolybiusSquare::parse(&text, variant)
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// The letters the rows and columns are labelled with.
+    pub fn labels(&self) -> &'static [char] {
+        self.variant.labels()
+    }
+
+    pub fn rows(&self) -> Vec<Vec<char>> {
+        self.cells
+            .chunks(self.labels().len())
+            .map(|row| row.to_vec())
+            .collect()
+    }
+
+    /// The row and column of `c`, if it is in the square.
+    pub fn position(&self, c: char) -> Option<(usize, usize)> {
+        let size = self.labels().len();
+        let index = self.cells.iter().position(|&cell| cell == c)?;
+        Some((index / size, index % size))
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> char {
+        self.cells[row * self.labels().len() + col]
+    }
+
+    /// The square as a table with the labels along the top and down the left, which
+    /// [`PolybiusSquare::parse`] reads back.
+    pub fn grid(&self) -> String {
+        let join = |chars: &[char]| {
+            chars
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut grid = format!("    {}\n", join(self.labels()));
+        for (label, row) in self.labels().iter().zip(self.rows()) {
+            grid.push_str(&format!("{label} | {}\n", join(&row)));
+        }
+        grid
+    }
+}
+
+/// Writes the cells row by row, the shortest form [`PolybiusSquare::parse`] reads back.
+impl fmt::Display for PolybiusSquare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.cells.iter().collect::<String>())
+    }
+}
+
+/// SplitMix64, a small generator that is fully determined by its seed.
+pub(crate) fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyed() {
+        let square = PolybiusSquare::keyed("privacy", Variant::Adfgvx);
+        assert_eq!("PRIVACYBDEFGHJKLMNOQSTUWXZ0123456789", square.to_string());
+        assert_eq!(Some((1, 1)), square.position('B'));
+        assert_eq!('B', square.get(1, 1));
+        let square = PolybiusSquare::keyed("jumbo", Variant::Adfgx);
+        assert_eq!("IUMBOACDEFGHKLNPQRSTVWXYZ", square.to_string());
+    }
+
+    #[test]
+    fn test_random() {
+        let square = PolybiusSquare::random(42, Variant::Adfgvx);
+        assert_eq!(square, PolybiusSquare::random(42, Variant::Adfgvx));
+        assert_ne!(square, PolybiusSquare::random(43, Variant::Adfgvx));
+        assert_eq!(
+            Ok(square.clone()),
+            PolybiusSquare::new(&square.to_string(), Variant::Adfgvx)
+        );
+        assert_eq!(
+            25,
+            PolybiusSquare::random(42, Variant::Adfgx).to_string().len()
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let square = PolybiusSquare::random(7, Variant::Adfgvx);
+        assert_eq!(
+            Ok(square.clone()),
+            PolybiusSquare::parse(&square.grid(), Variant::Adfgvx)
+        );
+        // Front-ends may join the lines of the grid with spaces.
+        assert_eq!(
+            Ok(square.clone()),
+            PolybiusSquare::parse(&square.grid().replace('\n', " "), Variant::Adfgvx)
+        );
+        assert_eq!(
+            Ok(square.clone()),
+            PolybiusSquare::parse(&square.to_string().to_lowercase(), Variant::Adfgvx)
+        );
+        assert!(square.grid().starts_with("    A D F G V X\nA | "));
+
+        let path = std::env::temp_dir().join("adfgvx_cipher_test_square.txt");
+        fs::write(&path, square.grid()).unwrap();
+        assert_eq!(
+            Ok(square),
+            PolybiusSquare::from_file(&path, Variant::Adfgvx)
+        );
+        fs::remove_file(&path).unwrap();
+        assert!(PolybiusSquare::from_file(&path, Variant::Adfgvx).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let cells = "PRIVACYBDEFGHJKLMNOQSTUWXZ012345678";
+        assert!(PolybiusSquare::new(cells, Variant::Adfgvx).is_err());
+        assert!(PolybiusSquare::new(&format!("{cells}9"), Variant::Adfgvx).is_ok());
+        assert!(PolybiusSquare::new(&format!("{cells}8"), Variant::Adfgvx).is_err());
+        assert!(PolybiusSquare::new(&format!("{cells}!"), Variant::Adfgvx).is_err());
+        assert!(PolybiusSquare::new(&format!("{cells}9"), Variant::Adfgx).is_err());
+    }
+}
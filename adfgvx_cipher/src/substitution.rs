@@ -0,0 +1,181 @@
+//! The substitution stage of the cipher on its own, a piece of text at a time. Unlike the
+//! transposition that follows it, it needs no more than one character of lookahead, so it can
+//! run over input of any length.
+use utils::cipher::CipherMode;
+use utils::error::CipherError;
+use utils::stream::StreamCipher;
+
+use crate::square::PolybiusSquare;
+
+/// Replaces each character with the labels of its row and column in the square, or reads label
+/// pairs back when decrypting. Whitespace is skipped both ways.
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    square: PolybiusSquare,
+    mode: CipherMode,
+    /// Characters read so far.
+    position: usize,
+    /// The row of a pair whose column has not been read yet, with its position.
+    row: Option<(usize, usize)>,
+    /// Characters left out besides whitespace.
+    skipped: &'static str,
+}
+
+impl Substitution {
+    pub fn new(square: PolybiusSquare, mode: CipherMode) -> Self {
+        Substitution {
+            square,
+            mode,
+            position: 0,
+            row: None,
+            skipped: "",
+        }
+    }
+
+    /// Also skips the characters in `skipped`, such as [`utils::PUNCTUATION`].
+    pub fn skipping(mut self, skipped: &'static str) -> Self {
+        self.skipped = skipped;
+        self
+    }
+
+    fn encrypt(&self, c: char, output: &mut String) -> Result<(), CipherError> {
+        let variant = self.square.variant();
+        let labels = variant.labels();
+        let normalized = variant.normalize(c);
+        let (row, col) = variant
+            .charset()
+            .contains(normalized)
+            .then(|| self.square.position(normalized))
+            .flatten()
+            .ok_or(CipherError::UnsupportedCharacter {
+                character: c,
+                position: self.position,
+            })?;
+        output.push(labels[row]);
+        output.push(labels[col]);
+        Ok(())
+    }
+
+    fn decrypt(&mut self, c: char, output: &mut String) -> Result<(), CipherError> {
+        let labels = self.square.labels();
+        // Labels are matched regardless of case, as the batch decryption does.
+        let label = c.to_ascii_uppercase();
+        let index = labels.iter().position(|&l| l == label).ok_or_else(|| {
+            let names: Vec<String> = labels.iter().map(|c| c.to_string()).collect();
+            CipherError::MalformedCiphertext {
+                position: self.position,
+                reason: format!(
+                    "{c:?} is not one of {} or {}",
+                    names[..names.len() - 1].join(", "),
+                    names[names.len() - 1]
+                ),
+            }
+        })?;
+        match self.row.take() {
+            Some((row, _)) => output.push(self.square.get(row, index)),
+            None => self.row = Some((index, self.position)),
+        }
+        Ok(())
+    }
+}
+
+impl StreamCipher for Substitution {
+    fn update(&mut self, input: &str, output: &mut String) -> Result<(), CipherError> {
+        for c in input.chars() {
+            if !c.is_whitespace() && !self.skipped.contains(c) {
+                match self.mode {
+                    CipherMode::Encrypt => self.encrypt(c, output)?,
+                    CipherMode::Decrypt => self.decrypt(c, output)?,
+                }
+            }
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _output: &mut String) -> Result<(), CipherError> {
+        match self.row {
+            Some((_, position)) => Err(CipherError::MalformedCiphertext {
+                position,
+                reason: "the last label has no partner".to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Variant;
+
+    fn run(mut stage: Substitution, text: &str) -> Result<String, CipherError> {
+        let mut output = String::new();
+        for c in text.chars() {
+            stage.update(c.encode_utf8(&mut [0; 4]), &mut output)?;
+        }
+        stage.finish(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_substitution() {
+        let square = PolybiusSquare::keyed("PRIVACY", Variant::Adfgvx);
+        let encrypt = Substitution::new(square.clone(), CipherMode::Encrypt);
+        let decrypt = || Substitution::new(square.clone(), CipherMode::Decrypt);
+        let intermediate = run(encrypt, "attack at 1200am").unwrap();
+        assert_eq!(28, intermediate.len());
+        // With a one column key the transposition leaves the intermediate text as it is.
+        assert_eq!(
+            crate::encrypt_with_square("attack at 1200am", &square, &[1], Default::default())
+                .unwrap(),
+            intermediate
+        );
+        assert_eq!("ATTACKAT1200AM", run(decrypt(), &intermediate).unwrap());
+        // Pairs are often written apart, which whitespace between them allows.
+        let spaced: Vec<&str> = (0..intermediate.len())
+            .step_by(2)
+            .map(|i| &intermediate[i..i + 2])
+            .collect();
+        assert_eq!(
+            "ATTACKAT1200AM",
+            run(decrypt(), &spaced.join(" \n")).unwrap()
+        );
+
+        let skipping = Substitution::new(square.clone(), CipherMode::Encrypt).skipping(".,");
+        assert_eq!(intermediate, run(skipping, "attack, at 1200am.").unwrap());
+
+        assert_eq!(
+            Err(CipherError::UnsupportedCharacter {
+                character: '!',
+                position: 6
+            }),
+            run(
+                Substitution::new(square.clone(), CipherMode::Encrypt),
+                "attack!"
+            )
+        );
+        assert!(matches!(
+            run(decrypt(), "AD AB"),
+            Err(CipherError::MalformedCiphertext { position: 4, .. })
+        ));
+        assert_eq!(
+            Err(CipherError::MalformedCiphertext {
+                position: 2,
+                reason: "the last label has no partner".to_string()
+            }),
+            run(decrypt(), "ADF")
+        );
+
+        let adfgx = PolybiusSquare::keyed("", Variant::Adfgx);
+        let intermediate = run(Substitution::new(adfgx.clone(), CipherMode::Encrypt), "Jig");
+        assert_eq!(
+            "IIG",
+            run(
+                Substitution::new(adfgx, CipherMode::Decrypt),
+                &intermediate.unwrap()
+            )
+            .unwrap()
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use adfgvx_cipher::Adfgvx;
+use morse_code::Morse;
+use utils::cipher::{Cipher, CipherMode, KeyField};
+use vigenere_cipher::Vigenere;
+
+/// Everything a front-end needs to list a cipher in a menu and build its input form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CipherInfo {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub key_fields: Vec<KeyField>,
+    pub modes: Vec<CipherMode>,
+}
+
+impl CipherInfo {
+    fn from_cipher(cipher: &dyn Cipher) -> Self {
+        CipherInfo {
+            id: cipher.id(),
+            name: cipher.name(),
+            description: cipher.description(),
+            key_fields: cipher.key_schema(),
+            modes: cipher.modes(),
+        }
+    }
+}
+
+/// The ciphers available to the front-ends, in menu order.
+///
+/// [`Registry::default`] contains every cipher in the workspace; new ciphers only have to be
+/// registered here to show up in both `encryptor` and `encryptor_tui`.
+#[derive(Debug)]
+pub struct Registry {
+    ciphers: Vec<Box<dyn Cipher>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register(Vigenere);
+        registry.register(Adfgvx);
+        registry.register(Morse);
+        registry
+    }
+}
+
+impl Registry {
+    /// Creates a registry without any ciphers in it.
+    pub fn new() -> Self {
+        Registry {
+            ciphers: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, cipher: impl Cipher + 'static) {
+        self.ciphers.push(Box::new(cipher));
+    }
+
+    pub fn ciphers(&self) -> &[Box<dyn Cipher>] {
+        &self.ciphers
+    }
+
+    /// Looks a cipher up by its [`Cipher::id`].
+    pub fn get(&self, id: &str) -> Option<&dyn Cipher> {
+        self.ciphers
+            .iter()
+            .find(|cipher| cipher.id() == id)
+            .map(|cipher| cipher.as_ref())
+    }
+
+    /// Describes every registered cipher.
+    pub fn list(&self) -> Vec<CipherInfo> {
+        self.ciphers
+            .iter()
+            .map(|cipher| CipherInfo::from_cipher(cipher.as_ref()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let registry = Registry::default();
+        assert_eq!(
+            Some("ADFGVX Cipher"),
+            registry.get("adfgvx").map(|c| c.name())
+        );
+        assert!(registry.get("enigma").is_none());
+    }
+
+    #[test]
+    fn test_list() {
+        let ids: Vec<&str> = Registry::default().list().iter().map(|c| c.id).collect();
+        assert_eq!(vec!["vigenere", "adfgvx", "morse"], ids);
+    }
+}
@@ -0,0 +1,523 @@
+use cipher_registry::Registry;
+use clap::{builder::PossibleValuesParser, value_parser, Arg, ArgAction, ArgMatches, Command};
+use morse_code::audio::{self, AudioSettings};
+use morse_code::transcribe::transcribe;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process::ExitCode;
+use utils::cipher::{Cipher, CipherMode, KeyField, KeyKind, Keys};
+use utils::postprocess::{self, PostProcessor};
+use utils::stream::{self, StreamCipher, StreamError, Trimmed};
+
+/// Exit code used when the cipher rejects the input or the keys.
+const EXIT_CIPHER_ERROR: u8 = 1;
+/// Exit code used when the input cannot be read or the output cannot be written.
+const EXIT_IO_ERROR: u8 = 74;
+
+/// The subcommand and its alias for each mode, e.g. `encrypt`/`encode`.
+fn mode_command(mode: CipherMode) -> (&'static str, &'static str) {
+    match mode {
+        CipherMode::Encrypt => ("encrypt", "encode"),
+        CipherMode::Decrypt => ("decrypt", "decode"),
+    }
+}
+
+fn key_arg(field: KeyField) -> Arg {
+    let mut help = field.label.to_string();
+    if let Some(hint) = field.hint() {
+        help.push_str(&format!(" [{hint}]"));
+    }
+    let arg = Arg::new(field.id)
+        .long(field.flag)
+        .help(help)
+        .required(field.validate(field.default_value()).is_err());
+    match field.kind {
+        KeyKind::Choice { options } => arg
+            .value_parser(PossibleValuesParser::new(options.iter().copied()))
+            .default_value(field.default_value()),
+        _ => arg.value_parser(move |value: &str| field.validate(value).map(|_| value.to_string())),
+    }
+}
+
+/// Options for Morse code as audio, only offered by the morse cipher: `encrypt` also writes a WAV
+/// file and `decrypt` transcribes one. The defaults match [`AudioSettings::default`].
+fn audio_args(mode: CipherMode) -> Vec<Arg> {
+    let number = |id: &'static str, flag: &'static str, help: &'static str| {
+        Arg::new(id)
+            .long(flag)
+            .value_parser(value_parser!(f64))
+            .help(help)
+    };
+    match mode {
+        CipherMode::Encrypt => vec![
+            Arg::new("wav")
+                .long("wav")
+                .value_name("FILE")
+                .help("Also write the Morse code as audio to the WAV file FILE"),
+            number("wpm", "wpm", "Speed of the characters in words per minute").default_value("20"),
+            number(
+                "farnsworth_wpm",
+                "farnsworth",
+                "Slower overall speed in words per minute, stretching the gaps between characters",
+            ),
+            number("frequency", "tone", "Pitch of the tone in Hz").default_value("600"),
+            Arg::new("sample_rate")
+                .long("sample-rate")
+                .value_parser(value_parser!(u32))
+                .help("Samples per second of the WAV file")
+                .default_value("44100"),
+        ],
+        CipherMode::Decrypt => vec![
+            Arg::new("wav")
+                .long("wav")
+                .value_name("FILE")
+                .conflicts_with("in")
+                .help(
+                    "Transcribe the Morse code in the mono WAV file FILE instead of reading text",
+                ),
+            number(
+                "frequency",
+                "tone",
+                "Pitch of the tone in Hz, searched for when not given",
+            ),
+        ],
+    }
+}
+
+/// Builds the command line parser, with one subcommand per registered cipher and one
+/// subcommand per mode below that.
+pub fn command(registry: &Registry) -> Command {
+    let mut command = Command::new("encryptor")
+        .about("Encrypt and decrypt text with classical ciphers")
+        .long_about(
+            "Encrypt and decrypt text with classical ciphers.\n\n\
+             Run without arguments to start the interactive wizard. Input is read from stdin and \
+             output written to stdout unless --in/--out are given. Exits with 1 when the cipher \
+             rejects the input and 74 when reading or writing fails.",
+        );
+    for info in registry.list() {
+        let mut cipher_command = Command::new(info.id)
+            .about(info.description)
+            .subcommand_required(true)
+            .arg_required_else_help(true);
+        for mode in info.modes {
+            let (name, alias) = mode_command(mode);
+            let mut mode_command = Command::new(name)
+                .visible_alias(alias)
+                .about(mode.name())
+                .args(info.key_fields.iter().cloned().map(key_arg))
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .value_name("FILE")
+                        .help("Read the input from FILE instead of stdin"),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_name("FILE")
+                        .help("Write the output to FILE instead of stdout"),
+                );
+            if mode == CipherMode::Decrypt {
+                mode_command = mode_command
+                    .arg(
+                        Arg::new("restore_spacing")
+                            .long("restore-spacing")
+                            .action(ArgAction::SetTrue)
+                            .help("Put the spaces between the words of the plaintext back"),
+                    )
+                    .arg(
+                        Arg::new("postprocess_endpoint")
+                            .long("postprocess-endpoint")
+                            .value_name("URL")
+                            .help("Have the HTTP service at URL tidy the plaintext up instead"),
+                    )
+                    .arg(
+                        Arg::new("postprocess_api_key")
+                            .long("postprocess-api-key")
+                            .value_name("KEY")
+                            .requires("postprocess_endpoint")
+                            .help(
+                                "Send KEY as a bearer token to the post-processing service \
+                                 [default: $POSTPROCESS_API_KEY]",
+                            ),
+                    );
+            }
+            if info.id == "morse" {
+                mode_command = mode_command.args(audio_args(mode));
+            }
+            cipher_command = cipher_command.subcommand(mode_command);
+        }
+        command = command.subcommand(cipher_command);
+    }
+    command
+}
+
+/// Runs the cipher selected on the command line.
+pub fn run(registry: &Registry, matches: &ArgMatches) -> ExitCode {
+    let Some((id, cipher_matches)) = matches.subcommand() else {
+        return ExitCode::SUCCESS;
+    };
+    let Some(cipher) = registry.get(id) else {
+        return ExitCode::from(EXIT_CIPHER_ERROR);
+    };
+    let Some((mode, mode_matches)) = cipher_matches.subcommand() else {
+        return ExitCode::SUCCESS;
+    };
+    let mode = if mode == mode_command(CipherMode::Encrypt).0 {
+        CipherMode::Encrypt
+    } else {
+        CipherMode::Decrypt
+    };
+
+    match run_cipher(cipher, mode, mode_matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err((message, code)) => {
+            eprintln!("Error: {message}");
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn run_cipher(
+    cipher: &dyn Cipher,
+    mode: CipherMode,
+    matches: &ArgMatches,
+) -> Result<(), (String, u8)> {
+    let mut keys = Keys::new();
+    for field in cipher.key_schema() {
+        if let Some(value) = matches.get_one::<String>(field.id) {
+            keys.insert(field.id.to_string(), value.clone());
+        }
+    }
+
+    let wav = matches.try_get_one::<String>("wav").ok().flatten();
+    let post_processor = post_processor(mode, matches);
+    if wav.is_none() && post_processor.is_none() && !overwrites_input(matches) {
+        let stream = cipher
+            .stream(mode, &keys)
+            .map_err(|e| (e.to_string(), EXIT_CIPHER_ERROR))?;
+        if let Some(stream) = stream {
+            return run_stream(stream, matches);
+        }
+    }
+
+    let input = match (mode, wav) {
+        (CipherMode::Decrypt, Some(path)) => read_audio(path, matches)?,
+        _ => match matches.get_one::<String>("in") {
+            Some(path) => fs::read_to_string(path),
+            None => {
+                let mut input = String::new();
+                io::stdin().read_to_string(&mut input).map(|_| input)
+            }
+        }
+        .map_err(|e| (format!("could not read the input: {e}"), EXIT_IO_ERROR))?,
+    };
+
+    let mut output = match mode {
+        CipherMode::Encrypt => cipher.encrypt(&input, &keys),
+        CipherMode::Decrypt => cipher.decrypt(&input, &keys),
+    }
+    .map_err(|e| (e.to_string(), EXIT_CIPHER_ERROR))?;
+    if let Some(post_processor) = post_processor {
+        output = post_processor.process(&output).map_err(|e| {
+            (
+                format!("could not post-process the plaintext: {e}"),
+                EXIT_IO_ERROR,
+            )
+        })?;
+    }
+
+    if let (CipherMode::Encrypt, Some(path)) = (mode, wav) {
+        write_audio(&output, path, matches)?;
+    }
+
+    let output = format!("{}\n", output.trim());
+    match matches.get_one::<String>("out") {
+        Some(path) => fs::write(path, output),
+        None => {
+            print!("{output}");
+            Ok(())
+        }
+    }
+    .map_err(|e| (format!("could not write the output: {e}"), EXIT_IO_ERROR))
+}
+
+/// The post-processor asked for when decrypting: the HTTP service given with
+/// `--postprocess-endpoint`, or the offline word segmenter with `--restore-spacing`.
+fn post_processor(mode: CipherMode, matches: &ArgMatches) -> Option<Box<dyn PostProcessor>> {
+    if mode != CipherMode::Decrypt {
+        return None;
+    }
+    let endpoint = matches.get_one::<String>("postprocess_endpoint");
+    let api_key = matches
+        .get_one::<String>("postprocess_api_key")
+        .cloned()
+        .or_else(|| env::var("POSTPROCESS_API_KEY").ok());
+    (endpoint.is_some() || matches.get_flag("restore_spacing"))
+        .then(|| postprocess::post_processor(endpoint.map(String::as_str), api_key.as_deref()))
+}
+
+/// Whether `--out` names the `--in` file, which streaming would truncate before reading it.
+fn overwrites_input(matches: &ArgMatches) -> bool {
+    let (Some(input), Some(output)) = (
+        matches.get_one::<String>("in"),
+        matches.get_one::<String>("out"),
+    ) else {
+        return false;
+    };
+    input == output
+        || fs::canonicalize(input)
+            .and_then(|input| Ok(input == fs::canonicalize(output)?))
+            .unwrap_or_default()
+}
+
+/// Runs `stream` from the input to the output as the text is read, so that files of any size are
+/// handled in constant memory. The output is trimmed and ends in a newline, as for the other
+/// ciphers.
+fn run_stream(stream: Box<dyn StreamCipher>, matches: &ArgMatches) -> Result<(), (String, u8)> {
+    let reader: Box<dyn Read> = match matches.get_one::<String>("in") {
+        Some(path) => Box::new(
+            File::open(path)
+                .map_err(|e| (format!("could not read the input: {e}"), EXIT_IO_ERROR))?,
+        ),
+        None => Box::new(io::stdin().lock()),
+    };
+    let mut writer: Box<dyn Write> = match matches.get_one::<String>("out") {
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).map_err(|e| {
+                (format!("could not write the output: {e}"), EXIT_IO_ERROR)
+            })?))
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+    stream::transform(reader, &mut writer, &mut Trimmed::new(stream))
+        .and_then(|()| Ok(writeln!(writer).and_then(|()| writer.flush())?))
+        .map_err(|e| match e {
+            StreamError::Io(e) => (format!("could not stream the text: {e}"), EXIT_IO_ERROR),
+            StreamError::Cipher(e) => (e.to_string(), EXIT_CIPHER_ERROR),
+        })
+}
+
+fn write_audio(morse: &str, path: &str, matches: &ArgMatches) -> Result<(), (String, u8)> {
+    let settings = AudioSettings {
+        wpm: matches.get_one::<f64>("wpm").copied().unwrap_or_default(),
+        farnsworth_wpm: matches.get_one::<f64>("farnsworth_wpm").copied(),
+        frequency: matches
+            .get_one::<f64>("frequency")
+            .copied()
+            .unwrap_or_default(),
+        sample_rate: matches
+            .get_one::<u32>("sample_rate")
+            .copied()
+            .unwrap_or_default(),
+        ..AudioSettings::default()
+    };
+    let samples =
+        audio::synthesize(morse, &settings).map_err(|e| (e.to_string(), EXIT_CIPHER_ERROR))?;
+    File::create(path)
+        .and_then(|file| audio::write_wav(BufWriter::new(file), &samples, settings.sample_rate))
+        .map_err(|e| (format!("could not write {path}: {e}"), EXIT_IO_ERROR))
+}
+
+/// Transcribes a WAV file to Morse code, reporting the tone and speed that were heard on stderr.
+fn read_audio(path: &str, matches: &ArgMatches) -> Result<String, (String, u8)> {
+    let (samples, sample_rate) = File::open(path)
+        .and_then(|file| audio::read_wav(BufReader::new(file)))
+        .map_err(|e| (format!("could not read {path}: {e}"), EXIT_IO_ERROR))?;
+    let frequency = matches.get_one::<f64>("frequency").copied();
+    let transcription = transcribe(&samples, sample_rate, frequency)
+        .map_err(|e| (e.to_string(), EXIT_CIPHER_ERROR))?;
+    eprintln!(
+        "Heard a {:.0} Hz tone at {:.0} wpm",
+        transcription.frequency, transcription.wpm
+    );
+    for unknown in &transcription.unknown {
+        eprintln!(
+            "Could not read the code group {} at position {} of the Morse code",
+            unknown.code, unknown.position
+        );
+    }
+    Ok(transcription.morse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_command() {
+        command(&Registry::default()).debug_assert();
+    }
+
+    #[test]
+    fn test_parse_columns() {
+        let matches = command(&Registry::default())
+            .try_get_matches_from(["encryptor", "adfgvx", "decrypt", "--columns", "5,1,3,4,2,6"])
+            .unwrap();
+        let (_, cipher_matches) = matches.subcommand().unwrap();
+        let (mode, mode_matches) = cipher_matches.subcommand().unwrap();
+        assert_eq!("decrypt", mode);
+        assert_eq!(
+            Some(&String::from("5,1,3,4,2,6")),
+            mode_matches.get_one::<String>("column_key")
+        );
+    }
+
+    #[test]
+    fn test_parse_postprocess() {
+        let command = command(&Registry::default());
+        let matches = command
+            .clone()
+            .try_get_matches_from([
+                "encryptor",
+                "vigenere",
+                "decrypt",
+                "--postprocess-endpoint",
+                "http://localhost:8080/format",
+            ])
+            .unwrap();
+        let (_, cipher_matches) = matches.subcommand().unwrap();
+        let (_, mode_matches) = cipher_matches.subcommand().unwrap();
+        assert!(post_processor(CipherMode::Decrypt, mode_matches).is_some());
+        let matches = command
+            .clone()
+            .try_get_matches_from(["encryptor", "vigenere", "decrypt"])
+            .unwrap();
+        let (_, cipher_matches) = matches.subcommand().unwrap();
+        let (_, mode_matches) = cipher_matches.subcommand().unwrap();
+        assert!(post_processor(CipherMode::Decrypt, mode_matches).is_none());
+        assert!(command
+            .clone()
+            .try_get_matches_from([
+                "encryptor",
+                "vigenere",
+                "decrypt",
+                "--postprocess-endpoint",
+                "http://localhost:8080/format",
+                "--postprocess-api-key",
+                "s3cret",
+            ])
+            .is_ok());
+        assert!(command
+            .clone()
+            .try_get_matches_from([
+                "encryptor",
+                "vigenere",
+                "decrypt",
+                "--postprocess-api-key",
+                "s3cret",
+            ])
+            .is_err());
+        assert!(command
+            .try_get_matches_from([
+                "encryptor",
+                "vigenere",
+                "encrypt",
+                "--postprocess-endpoint",
+                "http://localhost:8080/format",
+            ])
+            .is_err());
+    }
+
+    /// A directory of its own under the system temp directory, removed with everything in it when
+    /// dropped, also when the test fails.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "encryptor_test_{}_{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_stream_file() {
+        let registry = Registry::default();
+        let cipher = registry.get("adfgvx").unwrap();
+        let dir = TempDir::new();
+        let plaintext = dir.0.join("plaintext.txt");
+        let ciphertext = dir.0.join("ciphertext.txt");
+        let decrypted = dir.0.join("decrypted.txt");
+        fs::write(&plaintext, "Attack at 1200am.\n".repeat(10_000)).unwrap();
+        let run = |mode: CipherMode, input: &std::path::Path, output: &std::path::Path| {
+            let matches = command(&registry)
+                .try_get_matches_from([
+                    "encryptor",
+                    "adfgvx",
+                    mode_command(mode).0,
+                    "--key",
+                    "privacy",
+                    "--columns",
+                    "1",
+                    "--in",
+                    input.to_str().unwrap(),
+                    "--out",
+                    output.to_str().unwrap(),
+                ])
+                .unwrap();
+            let (_, cipher_matches) = matches.subcommand().unwrap();
+            let (_, mode_matches) = cipher_matches.subcommand().unwrap();
+            run_cipher(cipher, mode, mode_matches).map(|()| fs::read_to_string(output).unwrap())
+        };
+        let mut keys = Keys::new();
+        keys.insert("keyword".to_string(), "privacy".to_string());
+        keys.insert("column_key".to_string(), "1".to_string());
+        assert!(cipher.stream(CipherMode::Encrypt, &keys).unwrap().is_some());
+        let text = fs::read_to_string(&plaintext).unwrap();
+        let encrypted = run(CipherMode::Encrypt, &plaintext, &ciphertext).unwrap();
+        assert_eq!(
+            format!("{}\n", cipher.encrypt(&text, &keys).unwrap()),
+            encrypted
+        );
+        assert_eq!(
+            format!("{}\n", "ATTACKAT1200AM".repeat(10_000)),
+            run(CipherMode::Decrypt, &ciphertext, &decrypted).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_audio() {
+        let command = command(&Registry::default());
+        let matches = command
+            .clone()
+            .try_get_matches_from(["encryptor", "morse", "encode", "--wav", "out.wav"])
+            .unwrap();
+        let (_, cipher_matches) = matches.subcommand().unwrap();
+        let (_, mode_matches) = cipher_matches.subcommand().unwrap();
+        assert_eq!(Some(&20.0), mode_matches.get_one::<f64>("wpm"));
+        assert!(command
+            .clone()
+            .try_get_matches_from(["encryptor", "morse", "decode", "--wav", "in.wav"])
+            .is_ok());
+        assert!(command
+            .clone()
+            .try_get_matches_from([
+                "encryptor",
+                "morse",
+                "decode",
+                "--wav",
+                "a.wav",
+                "--in",
+                "b"
+            ])
+            .is_err());
+        assert!(command
+            .try_get_matches_from(["encryptor", "vigenere", "encode", "--wav", "out.wav"])
+            .is_err());
+    }
+}
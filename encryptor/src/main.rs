@@ -1,15 +1,26 @@
-#![allow(unused_assignments)]
-use adfgvx_cipher::*;
+use cipher_registry::Registry;
 use dialoguer::Input;
-use inquire::error::InquireError;
-use morse_code::*;
-use std::collections::HashMap;
+use inquire::{error::InquireError, list_option::ListOption};
 use std::env;
 use std::fs;
 use std::io::{self};
-use utils::*;
-use vigenere_cipher::*;
-fn main() {
+use std::process::{self, ExitCode};
+use utils::cipher::{Cipher, CipherMode, KeyKind, Keys};
+use utils::postprocess::post_processor;
+mod cli;
+
+fn main() -> ExitCode {
+    let registry = Registry::default();
+    if env::args_os().len() > 1 {
+        let matches = cli::command(&registry).get_matches();
+        return cli::run(&registry, &matches);
+    }
+    interactive(&registry);
+    ExitCode::SUCCESS
+}
+
+/// The step by step wizard used when `encryptor` is started without arguments.
+fn interactive(registry: &Registry) {
     println!(
         "{:^100}",
         "
@@ -24,452 +35,143 @@ fn main() {
     );
     println!("-----------------------------------------------------------------------------------------------------------------");
     println!("-----------------------------------------------------------------------------------------------------------------");
-    let available_methods = HashMap::from([
-        (0, vigenere as fn()),
-        (1, adfgvx as fn()),
-        (2, morse_code as fn()),
-    ]);
-    let items = vec!["Vigenère Cipher", "ADFGVX Cipher", "Morse Code"];
+    let items: Vec<String> = registry
+        .list()
+        .iter()
+        .map(|info| format!("{} - {}", info.name, info.description))
+        .collect();
 
-    let ans: Result<&str, InquireError> =
-        inquire::Select::new("What ecryption method do you choose?", items.clone()).prompt();
-    let mut selection: usize = 0;
+    let ans: Result<ListOption<String>, InquireError> =
+        inquire::Select::new("What ecryption method do you choose?", items).raw_prompt();
     match ans {
-        Ok(choice) => selection = items.iter().position(|x| &choice == x).unwrap(),
+        Ok(choice) => run(registry.ciphers()[choice.index].as_ref()),
         Err(_) => println!("There was an error, please try again"),
     }
-    available_methods.get(&selection).unwrap()()
 }
-fn print_table() {
-    let alphabet: Vec<char> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().collect();
-    let numbers: Vec<char> = "0123456789".chars().collect();
-
-    // Combine alphabet and numbers into a single character set
-    let full_set: Vec<char> = alphabet.iter().chain(numbers.iter()).copied().collect();
-
-    // Number of characters in the combined set
-    let set_length = full_set.len();
-
-    // Generate the Vigenère table
-    let mut vigenere_table: Vec<Vec<char>> = Vec::new();
-
-    for i in 0..set_length {
-        let mut row: Vec<char> = Vec::new();
-        for j in 0..set_length {
-            row.push(full_set[(i + j) % set_length]);
-        }
-        vigenere_table.push(row);
-    }
-
-    // Print the column headers
-    print!("   | "); // Padding for the row header and column separator
-    for &c in &full_set {
-        print!("{:>2} ", c);
-    }
-    println!();
-
-    // Print a separator line
-    print!("---+-"); // Row header and column separator
-    for _ in 0..set_length {
-        print!("---");
-    }
-    println!();
-
-    // Print the table rows with row headers and a vertical separator
-    for (i, row) in vigenere_table.iter().enumerate() {
-        print!("{:>2} | ", full_set[i]); // Row header with a vertical separator
-        for &c in row {
-            print!("{:>2} ", c);
-        }
-        println!();
-    }
-}
-
-fn vigenere() {
-    print_table();
-    println!("-----------------------------------------------------------------------------------------------------------------");
-    println!("-----------------------------------------------------------------------------------------------------------------\n\n");
-    let mut mode = String::new();
-    println!("Encryption Mode/Decryption Mode [e/D]:");
-    io::stdin()
-        .read_line(&mut mode)
-        .expect("Error reading input");
-
-    println!("Enter Keyword:");
-    let mut keyword = String::new();
-    io::stdin()
-        .read_line(&mut keyword)
-        .expect("Error reading line");
-    println!("{:?}", mode.trim());
-    if mode.trim() == "e" {
-        let mut plaintext = String::new();
-        if env::var("READ_FROM_FILE").is_ok() {
-            println!("\nEnter filename/path:");
-            let mut filepath = String::new();
-            io::stdin()
-                .read_line(&mut filepath)
-                .expect("Error reading input");
-            plaintext = fs::read_to_string(filepath.trim()).expect("error reading file");
-        } else {
-            println!("Enter plaintext:");
-            io::stdin()
-                .read_line(&mut plaintext)
-                .expect("Error reading input from user.");
-        }
-        remove_whitespace(&mut plaintext);
-        remove_whitespace(&mut keyword);
-        let plaintext = remove_punctuation(&plaintext);
-        let mut keyword = remove_punctuation(&keyword);
-        println!("{}", plaintext);
-
-        let keyword_string = generate_keyword_string(&mut keyword, plaintext.len());
-
-        let encrypted_string = generate_cipher(plaintext, keyword_string);
-        println!("Encrypted text:");
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        println!("{}", encrypted_string.trim());
-        println!("-----------------------------------------------------------------------------------------------------------------\n\n");
-
-        let items = vec!["Yes", "No"];
-
-        let ans = inquire::Select::new("Write the encrypted text to file?", items.clone())
-            .prompt()
-            .unwrap();
 
-        if ans == "Yes" {
-            let mut filename: String = String::new();
-            println!("Name the output file [press ENTER for default]");
-            io::stdin()
-                .read_line(&mut filename)
-                .expect("Error reading input");
-            filename = filename.trim().to_string();
+fn run(cipher: &dyn Cipher) {
+    let modes = cipher.modes();
+    let items: Vec<&str> = modes.iter().map(|mode| mode.name()).collect();
 
-            if filename.is_empty() {
-                filename = "encrypted_text.txt".to_string();
-            } else {
-                filename.push_str(".txt");
-            }
-
-            let temp = fs::write(filename, encrypted_string.trim());
-            match temp {
-                Ok(_) => {
-                    println!("File created successfully")
-                }
-                _ => {
-                    println!("Error while writing the output to file:")
-                }
-            }
-        }
-    } else {
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        println!("Ensure that the encrypted_string contains only [A-Z] and [0-9]");
-        println!("-----------------------------------------------------------------------------------------------------------------\n\n");
-        let mut encrypted_string = String::new();
-        if env::var("READ_FROM_FILE").is_ok() {
-            println!("\nEnter filename/path:");
-            let mut filepath = String::new();
-            io::stdin()
-                .read_line(&mut filepath)
-                .expect("Error reading input");
-            encrypted_string = fs::read_to_string(filepath.trim()).expect("error reading file");
-        } else {
-            println!("Enter encrypted text:");
-            io::stdin()
-                .read_line(&mut encrypted_string)
-                .expect("Error reading input from user.");
+    let mode = match inquire::Select::new("Select Mode", items).raw_prompt() {
+        Ok(choice) => modes[choice.index],
+        Err(_) => {
+            println!("There was an error, please try again");
+            return;
         }
-        let decrypted_string = decrypt(encrypted_string, keyword);
-        println!("Decrypted text:");
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        println!("{}", decrypted_string.trim());
-
-        println!("-----------------------------------------------------------------------------------------------------------------\n\n");
-        let items = vec!["Yes", "No"];
-
-        let ans = inquire::Select::new("Write the decrypted text to file?", items.clone())
-            .prompt()
-            .unwrap();
-
-        if ans == "Yes" {
-            let mut filename: String = String::new();
-            println!("Name the output file [press ENTER for default]");
-            io::stdin()
-                .read_line(&mut filename)
-                .expect("Error reading input");
-            filename = filename.trim().to_string();
-
-            if filename.is_empty() {
-                filename = "decrypted_string.txt".to_string();
-            } else {
-                filename.push_str(".txt");
-            }
+    };
 
-            let temp = fs::write(filename, decrypted_string.trim());
-            match temp {
-                Ok(_) => {
-                    println!("File created successfully")
-                }
-                _ => {
-                    println!("Error while writing the output to file:")
-                }
-            }
-        }
-    }
-}
-
-fn adfgvx() {
-    let items = vec!["Encryption Mode", "Decryption Mode"];
-
-    let mode = inquire::Select::new("Select Mode", items.clone())
-        .prompt()
-        .unwrap();
-
-    let mut plaintext = String::new();
+    let mut input_text = String::new();
     if env::var("READ_FROM_FILE").is_ok() {
         println!("\nEnter filename/path: ");
         let mut filepath = String::new();
         io::stdin()
             .read_line(&mut filepath)
             .expect("Error reading input");
-        plaintext = fs::read_to_string(filepath.trim())
+        input_text = fs::read_to_string(filepath.trim())
             .expect("error reading file")
             .trim()
             .to_string();
-    } else {
+    } else if mode == CipherMode::Encrypt {
         println!("\nEnter plaintext: ");
         io::stdin()
-            .read_line(&mut plaintext)
+            .read_line(&mut input_text)
+            .expect("Error reading input from user.");
+    } else {
+        println!("\nEnter encrypted text: ");
+        io::stdin()
+            .read_line(&mut input_text)
             .expect("Error reading input from user.");
     }
 
-    let key: String = Input::new()
-        .with_prompt("Enter encryption key")
-        .interact_text()
-        .unwrap();
-    let column_key: String = Input::new()
-        .with_prompt("Enter second key (Please enter numbers from 1-6 separated by whitespaces)")
-        .interact_text()
-        .unwrap();
-
-    let plaintext = remove_punctuation(&plaintext);
-    let mut key = remove_punctuation(&key);
-    let key = remove_whitespace(&mut key);
-
-    let column_key: Vec<u8> = column_key
-        .split_whitespace()
-        .map(|x| {let y:u8 = x.parse().unwrap(); y})
-        .collect();
-    let square = polybius_square(key.clone());
-    let square = polybius_to_string(square);
-    if mode == "Encryption Mode" {
-        let encrypted_string = encrypt_adfgvx(plaintext, key.clone(), column_key);
-
-        println!("\n{}", square);
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        println!("{}", encrypted_string);
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        let items = vec!["Yes", "No"];
-
-        let ans = inquire::Select::new("Write the encrypted text to file?", items.clone())
-            .prompt()
-            .unwrap();
-
-        if ans == "Yes" {
-            let mut filename: String = String::new();
-            println!("Name the output file [press ENTER for default]");
-            io::stdin()
-                .read_line(&mut filename)
-                .expect("Error reading input");
-            filename = filename.trim().to_string();
+    let mut keys = Keys::new();
+    for field in cipher.key_schema() {
+        let prompt = match field.hint() {
+            Some(hint) => format!("{} [{}]", field.label, hint),
+            None => field.label.to_string(),
+        };
+        let value: String = match field.kind {
+            KeyKind::Choice { options } => inquire::Select::new(&prompt, options.to_vec())
+                .prompt()
+                .unwrap()
+                .to_string(),
+            _ => Input::new()
+                .with_prompt(prompt)
+                .allow_empty(field.validate("").is_ok())
+                .validate_with(|value: &String| field.validate(value))
+                .interact_text()
+                .unwrap(),
+        };
+        keys.insert(field.id.to_string(), value);
+    }
+    if let Some(material) = cipher.key_material(&keys) {
+        println!("\n{material}");
+    }
 
-            if filename.is_empty() {
-                filename = "encrypted_text.txt".to_string();
-            } else {
-                filename.push_str(".txt");
-            }
-            /*match custom_filename {
-                Ok(file) => filename = file,
-                _ => filename = "encrypted_text.txt",
-            }*/
-            let temp = fs::write(filename, encrypted_string.trim());
-            match temp {
-                Ok(_) => {
-                    println!("File created successfully")
-                }
-                _ => {
-                    println!("Error while writing the output to file:")
-                }
-            }
+    let (output, default_filename) = match mode {
+        CipherMode::Encrypt => (cipher.encrypt(&input_text, &keys), "encrypted_text.txt"),
+        CipherMode::Decrypt => (cipher.decrypt(&input_text, &keys), "decrypted_text.txt"),
+    };
+    let mut output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            process::exit(1);
         }
-    } else {
-        let decrypted_string = decrypt_adfgvx(plaintext, key, column_key);
+    };
 
-        println!("\n{}", square);
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        println!("{}", decrypted_string);
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        let items = vec!["Yes", "No"];
+    println!("-----------------------------------------------------------------------------------------------------------------");
+    println!("{}", output.trim());
+    println!("-----------------------------------------------------------------------------------------------------------------\n\n");
+    let items = vec!["Yes", "No"];
 
-        let ans = inquire::Select::new("Write the decrypted text to file?", items.clone())
+    if mode == CipherMode::Decrypt
+        && inquire::Select::new("Restore spacing?", items.clone())
             .prompt()
-            .unwrap();
-
-        if ans == "Yes" {
-            let mut filename: String = String::new();
-            println!("Name the output file [press ENTER for default]");
-            io::stdin()
-                .read_line(&mut filename)
-                .expect("Error reading input");
-            filename = filename.trim().to_string();
-
-            if filename.is_empty() {
-                filename = "decrypted_text.txt".to_string();
-            } else {
-                filename.push_str(".txt");
+            .unwrap()
+            == "Yes"
+    {
+        // Set POSTPROCESS_ENDPOINT to have an HTTP service tidy the text up instead, and
+        // POSTPROCESS_API_KEY if it needs a bearer token.
+        let endpoint = env::var("POSTPROCESS_ENDPOINT").ok();
+        let api_key = env::var("POSTPROCESS_API_KEY").ok();
+        output = match post_processor(endpoint.as_deref(), api_key.as_deref()).process(&output) {
+            Ok(processed) => processed,
+            Err(e) => {
+                eprintln!("Could not post-process the plaintext: {e}");
+                output
             }
-            /*match custom_filename {
-                Ok(file) => filename = file,
-                _ => filename = "encrypted_text.txt",
-            }*/
-            let temp = fs::write(filename, decrypted_string.trim());
-            match temp {
-                Ok(_) => {
-                    println!("File created successfully")
-                }
-                _ => {
-                    println!("Error while writing the output to file:")
-                }
-            }
-        }
-    }
-}
-fn polybius_to_string(square: Vec<Vec<char>>) -> String {
-    let headers = "ADFGVX"; // Header characters
-    let c_headers = "   A D F G V X"; // Header characters
-    let size = square.len();
-
-    // Create column headers
-    let column_headers: String = c_headers.chars().collect::<String>();
-
-    // Add column headers and separator
-    let mut result = format!("{:<15}   \n   {}\n", column_headers, "-".repeat(size * 2));
-    dbg!("{}", &result);
-
-    for (i, row) in square.iter().enumerate() {
-        // Use the corresponding row header (A, D, F, G, V, X)
-        let row_header = headers.chars().nth(i).unwrap_or(' ');
-        let row_string: String = row
-            .iter()
-            .map(|c| c.to_string())
-            .collect::<Vec<String>>()
-            .join(" "); // Add spaces between elements
-        result.push_str(&format!("{} | {}\n", row_header, row_string)); // Add row header and row data
+        };
+        println!("-----------------------------------------------------------------------------------------------------------------");
+        println!("{}", output.trim());
+        println!("-----------------------------------------------------------------------------------------------------------------\n\n");
     }
 
-    result.to_string()
-}
-
-fn morse_code() {
-    let items = vec!["Encryption Mode", "Decryption Mode"];
-
-    let mode = inquire::Select::new("Select Mode", items.clone())
+    let ans = inquire::Select::new("Write the output to file?", items.clone())
         .prompt()
         .unwrap();
 
-    let mut plaintext = String::new();
-    if env::var("READ_FROM_FILE").is_ok() {
-        println!("\nEnter filename/path: ");
-        let mut filepath = String::new();
+    if ans == "Yes" {
+        let mut filename: String = String::new();
+        println!("Name the output file [press ENTER for default]");
         io::stdin()
-            .read_line(&mut filepath)
+            .read_line(&mut filename)
             .expect("Error reading input");
-        plaintext = fs::read_to_string(filepath.trim())
-            .expect("error reading file")
-            .trim()
-            .to_string();
-    } else {
-        println!("\nEnter plaintext: ");
-        io::stdin()
-            .read_line(&mut plaintext)
-            .expect("Error reading input from user.");
-    }
-
-    if mode == "Encryption Mode" {
-        let encrypted_string = encrypt_morse_code(plaintext);
-
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        println!("{}", encrypted_string);
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        let items = vec!["Yes", "No"];
-
-        let ans = inquire::Select::new("Write the encrypted text to file?", items.clone())
-            .prompt()
-            .unwrap();
+        filename = filename.trim().to_string();
 
-        if ans == "Yes" {
-            let mut filename: String = String::new();
-            println!("Name the output file [press ENTER for default]");
-            io::stdin()
-                .read_line(&mut filename)
-                .expect("Error reading input");
-            filename = filename.trim().to_string();
-
-            if filename.is_empty() {
-                filename = "encrypted_text.txt".to_string();
-            } else {
-                filename.push_str(".txt");
-            }
-            /*match custom_filename {
-                Ok(file) => filename = file,
-                _ => filename = "encrypted_text.txt",
-            }*/
-            let temp = fs::write(filename, encrypted_string.trim());
-            match temp {
-                Ok(_) => {
-                    println!("File created successfully")
-                }
-                _ => {
-                    println!("Error while writing the output to file:")
-                }
-            }
+        if filename.is_empty() {
+            filename = default_filename.to_string();
+        } else {
+            filename.push_str(".txt");
         }
-    } else {
-        let decrypted_string = decrypt_morse_code(plaintext);
-
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        println!("{}", decrypted_string);
-        println!("-----------------------------------------------------------------------------------------------------------------");
-        let items = vec!["Yes", "No"];
-
-        let ans = inquire::Select::new("Write the decrypted text to file?", items.clone())
-            .prompt()
-            .unwrap();
-
-        if ans == "Yes" {
-            let mut filename: String = String::new();
-            println!("Name the output file [press ENTER for default]");
-            io::stdin()
-                .read_line(&mut filename)
-                .expect("Error reading input");
-            filename = filename.trim().to_string();
 
-            if filename.is_empty() {
-                filename = "decrypted_text.txt".to_string();
-            } else {
-                filename.push_str(".txt");
+        let temp = fs::write(filename, output.trim());
+        match temp {
+            Ok(_) => {
+                println!("File created successfully")
             }
-            /*match custom_filename {
-                Ok(file) => filename = file,
-                _ => filename = "encrypted_text.txt",
-            }*/
-            let temp = fs::write(filename, decrypted_string.trim());
-            match temp {
-                Ok(_) => {
-                    println!("File created successfully")
-                }
-                _ => {
-                    println!("Error while writing the output to file:")
-                }
+            _ => {
+                println!("Error while writing the output to file:")
             }
         }
     }
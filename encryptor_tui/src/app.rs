@@ -1,17 +1,15 @@
+use cipher_registry::Registry;
 use cli_clipboard::ClipboardContext;
 use cli_clipboard::ClipboardProvider;
 use cli_log::debug;
 use color_eyre::Result;
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use ratatui::{
-    prelude::Backend,
-    style::Stylize,
-    text::Line,
-    widgets::{Block, ListState, Paragraph},
-    DefaultTerminal, Frame, Terminal,
-};
-use std::{env, fs, iter::Cycle, slice::Iter};
-use tui_textarea::{CursorMove, Input, Key, TextArea};
+
+use ratatui::{prelude::Backend, widgets::ListState, Terminal};
+use std::{env, fs};
+use tui_textarea::{Input, Key, TextArea};
+use utils::cipher::{Cipher, CipherMode, KeyKind, Keys};
+use utils::postprocess::post_processor;
 
 use crate::ui::ui;
 
@@ -24,16 +22,10 @@ pub enum CurrentScreen {
     Exiting,
 }
 
-#[derive(Debug)]
-pub enum EncryptionMethods {
-    VigenereCipher,
-    ADFGVX,
-    MorseCode,
-}
-
 #[derive(Debug)]
 pub struct EncryptionMethod {
     pub name: String,
+    pub description: String,
 }
 #[derive(Debug)]
 pub struct ChosenMethodList {
@@ -46,44 +38,56 @@ pub struct Mode {
     pub name: String,
 }
 
-#[derive(Debug)]
-pub enum SelectedMode {
-    Encrypt,
-    Decrypt,
-}
-
 #[derive(Debug)]
 pub struct Modes {
     pub items: Vec<Mode>,
     pub state: ListState,
-    pub selected_mode: SelectedMode,
+    /// The modes offered by the selected cipher, in the same order as `items`.
+    pub modes: Vec<CipherMode>,
+    pub selected_mode: CipherMode,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Inputs {
-    Keyword,
+    /// The key field at this index of the selected cipher's key schema.
+    Key(usize),
     InputText,
-    ColumnKey,
 }
 
 #[derive(Debug)]
 pub struct App<'a> {
     /// Is the application running?
     pub running: bool,
-    pub keyword: String,
-    pub column_key: Vec<u8>,
+    pub keys: Keys,
     pub plaintext: String,
     pub encrypted_string: String,
     pub write_to_file: bool,
     pub read_from_file: bool,
     pub current_screen: CurrentScreen,
-    pub encryption: EncryptionMethods,
+    pub registry: Registry,
+    pub encryption: usize,
     pub encryption_methods_list: ChosenMethodList,
     pub mode: Modes,
-    pub keyword_text_area: TextArea<'a>,
+    pub key_text_areas: Vec<TextArea<'a>>,
     pub input_text_area: TextArea<'a>,
-    pub column_key_text_area: TextArea<'a>,
     pub inputs: Vec<Inputs>,
+    /// The reason the last attempt to run the cipher failed, shown on the input screen.
+    pub error: Option<String>,
+    /// Whether empty key fields should be flagged too, set once the user tries to submit.
+    pub show_validation: bool,
+    /// Whether the decrypted text is shown with the spaces between words put back.
+    pub restore_spacing: bool,
+    /// The decrypted text with its spacing restored, worked out when [`App::restore_spacing`] is
+    /// switched on rather than on every redraw.
+    pub spaced_plaintext: String,
+    /// The table that helps when working the cipher by hand with the submitted keys, worked out
+    /// once on submit, see [`utils::cipher::Cipher::key_material`].
+    pub key_material: Option<String>,
+    /// HTTP service that tidies the decrypted text up in place of the offline word segmenter,
+    /// taken from `POSTPROCESS_ENDPOINT`.
+    pub postprocess_endpoint: Option<String>,
+    /// Bearer token for [`App::postprocess_endpoint`], taken from `POSTPROCESS_API_KEY`.
+    pub postprocess_api_key: Option<String>,
     pub keyword_input: Input,
     pub currently_editing: Inputs,
 }
@@ -91,54 +95,54 @@ pub struct App<'a> {
 impl Default for App<'_> {
     fn default() -> Self {
         let read_from_file = env::var("READ_FROM_FILE").is_ok();
-        let column_key: Vec<u8> = vec![1, 2, 3, 4, 5, 6];
+        let postprocess_endpoint = env::var("POSTPROCESS_ENDPOINT").ok();
+        let postprocess_api_key = env::var("POSTPROCESS_API_KEY").ok();
+        let registry = Registry::default();
+        let items = registry
+            .list()
+            .iter()
+            .map(|info| EncryptionMethod {
+                name: info.name.to_string(),
+                description: info.description.to_string(),
+            })
+            .collect();
         App {
             running: true,
-            keyword: String::new(),
-            column_key,
+            keys: Keys::new(),
             plaintext: String::new(),
             encrypted_string: String::new(),
             write_to_file: false,
             read_from_file,
             current_screen: CurrentScreen::ChoosingEncryption,
-            encryption: EncryptionMethods::VigenereCipher,
+            registry,
+            encryption: 0,
             encryption_methods_list: ChosenMethodList {
-                items: vec![
-                    EncryptionMethod {
-                        name: "Vigenere Cipher".to_string(),
-                    },
-                    EncryptionMethod {
-                        name: "ADFGVX Cipher".to_string(),
-                    },
-                    EncryptionMethod {
-                        name: "Morse Code".to_string(),
-                    },
-                ],
+                items,
                 state: ListState::default(),
             },
             mode: Modes {
-                items: vec![
-                    Mode {
-                        name: "Encryption Mode".to_string(),
-                    },
-                    Mode {
-                        name: "Decryption Mode".to_string(),
-                    },
-                ],
+                items: Vec::new(),
                 state: ListState::default(),
-                selected_mode: SelectedMode::Encrypt,
+                modes: Vec::new(),
+                selected_mode: CipherMode::Encrypt,
             },
-            keyword_text_area: TextArea::default(),
+            key_text_areas: Vec::new(),
             inputs: vec![Inputs::InputText],
+            error: None,
+            show_validation: false,
+            restore_spacing: false,
+            spaced_plaintext: String::new(),
+            key_material: None,
+            postprocess_endpoint,
+            postprocess_api_key,
             input_text_area: TextArea::default(),
-            column_key_text_area: TextArea::default(),
             keyword_input: Input {
                 key: Key::Char('a'),
                 ctrl: true,
                 alt: false,
                 shift: false,
             },
-            currently_editing: Inputs::Keyword,
+            currently_editing: Inputs::InputText,
         }
     }
 }
@@ -149,6 +153,11 @@ impl App<'_> {
         Self::default()
     }
 
+    /// The cipher chosen on the first screen.
+    pub fn cipher(&self) -> &dyn Cipher {
+        self.registry.ciphers()[self.encryption].as_ref()
+    }
+
     fn select_none(&mut self) {
         match self.current_screen {
             CurrentScreen::ChoosingEncryption => {
@@ -204,46 +213,42 @@ impl App<'_> {
     fn select_current(&mut self) {
         match self.current_screen {
             CurrentScreen::ChoosingEncryption => {
-                if self.encryption_methods_list.state.selected().is_some() {
-                    self.encryption = match self.encryption_methods_list.state.selected() {
-                        Some(0) => {
-                            self.inputs = vec![Inputs::Keyword, Inputs::InputText];
-                            EncryptionMethods::VigenereCipher
-                        }
-                        Some(1) => {
-                            self.inputs =
-                                vec![Inputs::Keyword, Inputs::InputText, Inputs::ColumnKey];
-                            EncryptionMethods::ADFGVX
-                        }
-                        Some(2) => {
-                            self.inputs = vec![Inputs::InputText];
-                            self.currently_editing = Inputs::InputText;
-                            EncryptionMethods::MorseCode
-                        }
-                        _ => EncryptionMethods::VigenereCipher,
-                    };
+                if let Some(selected) = self.encryption_methods_list.state.selected() {
+                    self.encryption = selected.min(self.registry.ciphers().len() - 1);
+                    let schema = self.cipher().key_schema();
+                    self.key_text_areas = schema
+                        .iter()
+                        .map(|field| TextArea::new(vec![field.default_value().to_string()]))
+                        .collect();
+                    self.inputs = (0..schema.len()).map(Inputs::Key).collect();
+                    self.inputs.push(Inputs::InputText);
+                    self.currently_editing = self.inputs[0].clone();
+                    self.mode.modes = self.cipher().modes();
+                    self.mode.items = self
+                        .mode
+                        .modes
+                        .iter()
+                        .map(|mode| Mode {
+                            name: mode.name().to_string(),
+                        })
+                        .collect();
                     self.current_screen = CurrentScreen::ChoosingMode
                 }
             }
-            CurrentScreen::ChoosingMode => {
-                if self.mode.state.selected().is_some() {
-                    self.mode.selected_mode = match self.mode.state.selected() {
-                        Some(0) => SelectedMode::Encrypt,
-                        Some(1) => SelectedMode::Decrypt,
-                        _ => SelectedMode::Encrypt,
-                    };
-                    self.current_screen = CurrentScreen::InputtingValues;
-                }
+            CurrentScreen::ChoosingMode if self.mode.state.selected().is_some() => {
+                self.mode.selected_mode = self
+                    .mode
+                    .state
+                    .selected()
+                    .and_then(|selected| self.mode.modes.get(selected).copied())
+                    .unwrap_or(CipherMode::Encrypt);
+                self.current_screen = CurrentScreen::InputtingValues;
             }
             _ => {}
         }
     }
     /// Run the application's main loop.
-    pub fn run<B: Backend>(
-        mut self,
-        mut terminal: &mut Terminal<B>,
-        events: &[Event],
-    ) -> Result<()> {
+    pub fn run<B: Backend>(mut self, terminal: &mut Terminal<B>, _events: &[Event]) -> Result<()> {
         self.running = true;
         while self.running {
             terminal.draw(|frame| ui(frame, &mut self).expect("REASON"))?;
@@ -252,27 +257,6 @@ impl App<'_> {
         Ok(())
     }
 
-    /// Renders the user interface.
-    ///
-    /// This is where you add new widgets. See the following resources for more information:
-    /// - <https://docs.rs/ratatui/latest/ratatui/widgets/index.html>
-    /// - <https://github.com/ratatui/ratatui/tree/master/examples>
-    fn draw(&mut self, frame: &mut Frame) {
-        let title = Line::from("Ratatui Simple Template")
-            .bold()
-            .blue()
-            .centered();
-        let text = "Hello, Ratatui!\n\n\
-            Created using https://github.com/ratatui/templates\n\
-            Press `Esc` to stop running.";
-        frame.render_widget(
-            Paragraph::new(text)
-                .block(Block::bordered().title(title))
-                .centered(),
-            frame.area(),
-        )
-    }
-
     /// Reads the crossterm events and updates the state of [`App`].
     ///
     /// If your application needs to perform work in between handling events, you can use the
@@ -286,65 +270,25 @@ impl App<'_> {
                     key: Key::Char('s') | Key::Char('S'),
                     ctrl: true,
                     ..
-                } => {
-                    if let CurrentScreen::InputtingValues = self.current_screen {
-                        let keyword_text = self.keyword_text_area.clone().into_lines().join(" ");
-                        debug!("Keyword: {:?}", keyword_text.clone());
-                        let column_key: Vec<u8> = self
-                            .column_key_text_area
-                            .clone()
-                            .into_lines()
-                            .join(" ")
-                            .split_whitespace()
-                            .enumerate()
-                            .map(|(i, x)| x.parse().unwrap_or(i as u8 + 1_u8))
-                            .collect();
-                        let mut input_text = self.input_text_area.clone().into_lines().join(" ");
-
-                        self.keyword = keyword_text;
-                        self.column_key = column_key;
-                        if self.read_from_file {
-                            input_text =
-                                fs::read_to_string(input_text.trim()).expect("Error reading file")
-                        }
-                        debug!("{}", input_text.clone());
-                        match self.mode.selected_mode {
-                            SelectedMode::Encrypt => {
-                                self.plaintext = input_text;
-                            }
-                            SelectedMode::Decrypt => self.encrypted_string = input_text,
-                        }
-                        self.current_screen = CurrentScreen::SeeingResult;
-                    }
-                }
+                } => self.submit_inputs(),
                 input => match input {
-                    Input { key: Key::Tab, .. } => {
-                        if let EncryptionMethods::MorseCode = self.encryption {
-                        } else {
-                            let index = self
-                                .inputs
-                                .clone()
-                                .into_iter()
-                                .position(|r| r == self.currently_editing)
-                                .expect("Error finding index");
-                            let next_element = if index < self.inputs.len() - 1 {
-                                self.inputs[index + 1].clone()
-                            } else {
-                                self.inputs[0].clone()
-                            };
-                            self.currently_editing = next_element;
-                        }
-                    }
+                    Input { key: Key::Tab, .. } => self.focus_next_input(),
                     _ => match self.currently_editing {
-                        Inputs::Keyword => {
-                            self.keyword_text_area.input(input);
+                        Inputs::Key(index) if self.choice_options(index).is_some() => {
+                            match input.key {
+                                Key::Left | Key::Up => self.cycle_choice(index, false),
+                                Key::Right | Key::Down | Key::Char(' ') => {
+                                    self.cycle_choice(index, true)
+                                }
+                                _ => {}
+                            }
+                        }
+                        Inputs::Key(index) => {
+                            self.key_text_areas[index].input(input);
                         }
                         Inputs::InputText => {
                             self.input_text_area.input(input);
                         }
-                        Inputs::ColumnKey => {
-                            self.column_key_text_area.input(input);
-                        }
                     },
                 },
             },
@@ -352,33 +296,19 @@ impl App<'_> {
                 Input { key: Key::Esc, .. } => self.quit(),
                 input => match input {
                     Input { key: Key::Tab, .. } => {
-                        if let EncryptionMethods::MorseCode = self.encryption {
-                        } else {
-                            let index = self
-                                .inputs
-                                .clone()
-                                .into_iter()
-                                .position(|r| r == self.currently_editing)
-                                .expect("Error finding index");
-                            let next_element = if index < self.inputs.len() - 1 {
-                                self.inputs[index + 1].clone()
-                            } else {
-                                self.inputs[0].clone()
-                            };
-                            self.currently_editing = next_element;
-                            debug!("changed currently editing");
-                        }
+                        self.focus_next_input();
+                        debug!("changed currently editing");
                     }
                     Input {
                         key: Key::Char('c') | Key::Char('C'),
                         ctrl: true,
                         ..
                     } => match self.currently_editing {
-                        Inputs::Keyword => {
-                            debug!("keyword");
-                            self.keyword_text_area.select_all();
-                            self.keyword_text_area.input(input);
-                            ctx.set_contents(self.keyword_text_area.lines().join(" "))
+                        Inputs::Key(index) => {
+                            debug!("key {}", index);
+                            self.key_text_areas[index].select_all();
+                            self.key_text_areas[index].input(input);
+                            ctx.set_contents(self.key_text_areas[index].lines().join(" "))
                                 .unwrap()
                         }
                         Inputs::InputText => {
@@ -387,35 +317,32 @@ impl App<'_> {
                             self.input_text_area.copy();
                             self.input_text_area.input(input);
                             match self.mode.selected_mode {
-                                SelectedMode::Encrypt => {
+                                CipherMode::Encrypt => {
                                     ctx.set_contents(self.encrypted_string.clone()).unwrap();
                                 }
-                                SelectedMode::Decrypt => {
-                                    ctx.set_contents(self.plaintext.clone()).unwrap();
+                                CipherMode::Decrypt => {
+                                    ctx.set_contents(self.result_text()).unwrap();
                                 }
                             }
                         }
-                        Inputs::ColumnKey => {
-                            debug!("c_key");
-                            ctx.set_contents(self.column_key_text_area.lines().join(" "))
-                                .unwrap();
-                            self.column_key_text_area.select_all();
-                            self.column_key_text_area.input(input);
-                        }
                     },
+                    Input {
+                        key: Key::Char('r') | Key::Char('R'),
+                        ctrl: true,
+                        ..
+                    } if self.mode.selected_mode == CipherMode::Decrypt => {
+                        self.toggle_spacing();
+                    }
                     Input {
                         key: Key::Up | Key::Down | Key::Right | Key::Left,
                         ..
                     } => match self.currently_editing {
-                        Inputs::Keyword => {
-                            self.keyword_text_area.input(input);
+                        Inputs::Key(index) => {
+                            self.key_text_areas[index].input(input);
                         }
                         Inputs::InputText => {
                             self.input_text_area.input(input);
                         }
-                        Inputs::ColumnKey => {
-                            self.column_key_text_area.input(input);
-                        }
                     },
                     _ => {}
                 },
@@ -423,108 +350,14 @@ impl App<'_> {
             _ => {
                 match event::read()? {
                     // it's important to check KeyEventKind::Press to avoid handling key release events
-                    Event::Key(key) => {
-                        if key.kind == KeyEventKind::Press {
-                            match (key.modifiers, key.code) {
-                                (_, KeyCode::Esc)
-                                | (
-                                    KeyModifiers::CONTROL,
-                                    KeyCode::Char('c') | KeyCode::Char('C'),
-                                ) => self.quit(),
-                                (
-                                    KeyModifiers::CONTROL,
-                                    KeyCode::Char('s') | KeyCode::Char('S'),
-                                ) => {
-                                    if let CurrentScreen::InputtingValues = self.current_screen {
-                                        let keyword_text =
-                                            self.keyword_text_area.clone().into_lines().join(" ");
-                                        let column_key: Vec<u8> = self
-                                            .column_key_text_area
-                                            .clone()
-                                            .into_lines()
-                                            .join(" ")
-                                            .split_whitespace()
-                                            .enumerate()
-                                            .map(|(i, x)| x.parse().unwrap_or(i as u8 + 1_u8))
-                                            .collect();
-                                        let mut input_text =
-                                            self.input_text_area.clone().into_lines().join(" ");
-
-                                        self.keyword = keyword_text;
-                                        self.column_key = column_key;
-                                        if self.read_from_file {
-                                            input_text = fs::read_to_string(input_text)
-                                                .expect("Error reading file")
-                                        }
-                                        match self.mode.selected_mode {
-                                            SelectedMode::Encrypt => {
-                                                self.plaintext = input_text;
-                                            }
-                                            SelectedMode::Decrypt => {
-                                                self.encrypted_string = input_text
-                                            }
-                                        }
-                                        self.current_screen = CurrentScreen::SeeingResult;
-                                    }
-                                }
-                                // Add other key handlers here.
-                                _ => {}
-                            }
-                            match self.current_screen {
-                                CurrentScreen::InputtingValues => match key.code {
-                                    KeyCode::Char(value) => match self.currently_editing {
-                                        Inputs::Keyword => {
-                                            self.keyword_text_area.insert_char(value);
-                                            self.keyword_text_area.move_cursor(CursorMove::Forward);
-                                        }
-                                        Inputs::InputText => {
-                                            self.input_text_area.insert_char(value);
-                                            self.input_text_area.move_cursor(CursorMove::Forward);
-                                        }
-                                        Inputs::ColumnKey => {
-                                            self.column_key_text_area.insert_char(value);
-                                            self.column_key_text_area
-                                                .move_cursor(CursorMove::Forward);
-                                        }
-                                    },
-                                    KeyCode::Backspace => match self.currently_editing {
-                                        Inputs::Keyword => {
-                                            self.keyword_text_area.delete_char();
-                                            self.keyword_text_area.move_cursor(CursorMove::Back);
-                                        }
-                                        Inputs::InputText => {
-                                            self.input_text_area.delete_char();
-                                            self.input_text_area.move_cursor(CursorMove::Back);
-                                        }
-                                        Inputs::ColumnKey => {
-                                            self.column_key_text_area.delete_char();
-                                            self.column_key_text_area.move_cursor(CursorMove::Back);
-                                        }
-                                    },
-                                    KeyCode::Esc => {
-                                        self.quit();
-                                    }
-                                    KeyCode::Tab => {
-                                        if let EncryptionMethods::MorseCode = self.encryption {
-                                        } else {
-                                            let index = self
-                                                .inputs
-                                                .clone()
-                                                .into_iter()
-                                                .position(|r| r == self.currently_editing)
-                                                .expect("Error finding index");
-                                            let next_element = if index < self.inputs.len() - 1 {
-                                                self.inputs[index + 1].clone()
-                                            } else {
-                                                self.inputs[0].clone()
-                                            };
-                                            self.currently_editing = next_element;
-                                        }
-                                    }
-                                    _ => {}
-                                },
-                                _ => self.on_key_event(key),
+                    Event::Key(key) if key.kind == KeyEventKind::Press => {
+                        match (key.modifiers, key.code) {
+                            (_, KeyCode::Esc)
+                            | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => {
+                                self.quit()
                             }
+                            // Add other key handlers here.
+                            _ => self.on_key_event(key),
                         }
                     }
                     Event::Mouse(_) => {}
@@ -537,6 +370,146 @@ impl App<'_> {
         Ok(())
     }
 
+    /// Moves the focus to the next input field, wrapping around at the end.
+    fn focus_next_input(&mut self) {
+        let index = self
+            .inputs
+            .iter()
+            .position(|r| *r == self.currently_editing)
+            .expect("Error finding index");
+        self.currently_editing = self.inputs[(index + 1) % self.inputs.len()].clone();
+    }
+
+    /// The options of the key field at `index` if it is a [`KeyKind::Choice`], which is picked
+    /// from rather than typed into.
+    pub fn choice_options(&self, index: usize) -> Option<&'static [&'static str]> {
+        match self.cipher().key_schema().get(index)?.kind {
+            KeyKind::Choice { options } => Some(options),
+            _ => None,
+        }
+    }
+
+    /// Moves the choice field at `index` to the next option, or the previous one unless `forward`
+    /// is set, wrapping around at either end.
+    fn cycle_choice(&mut self, index: usize, forward: bool) {
+        let Some(options) = self
+            .choice_options(index)
+            .filter(|options| !options.is_empty())
+        else {
+            return;
+        };
+        let value = self.key_text_areas[index].lines().join(" ");
+        let current = options
+            .iter()
+            .position(|option| option.eq_ignore_ascii_case(value.trim()))
+            .unwrap_or_default();
+        let next = match forward {
+            true => (current + 1) % options.len(),
+            false => (current + options.len() - 1) % options.len(),
+        };
+        self.key_text_areas[index] = TextArea::new(vec![options[next].to_string()]);
+    }
+
+    /// Switches between the decrypted text as it is and with its spacing restored by the
+    /// post-processor. If post-processing fails the text stays as it is and the reason is kept in
+    /// [`App::error`].
+    fn toggle_spacing(&mut self) {
+        self.error = None;
+        if self.restore_spacing {
+            self.restore_spacing = false;
+            return;
+        }
+        let post_processor = post_processor(
+            self.postprocess_endpoint.as_deref(),
+            self.postprocess_api_key.as_deref(),
+        );
+        match post_processor.process(&self.plaintext) {
+            Ok(spaced) => {
+                self.spaced_plaintext = spaced;
+                self.restore_spacing = true;
+            }
+            Err(e) => self.error = Some(format!("Could not post-process the text: {e}")),
+        }
+    }
+
+    /// The output shown on the result screen.
+    pub fn result_text(&self) -> String {
+        match self.mode.selected_mode {
+            CipherMode::Encrypt => self.encrypted_string.clone(),
+            CipherMode::Decrypt if self.restore_spacing => self.spaced_plaintext.clone(),
+            CipherMode::Decrypt => self.plaintext.clone(),
+        }
+    }
+
+    /// Validates every key field against the selected cipher's schema. Fields that are still empty
+    /// are only reported once the user has tried to submit.
+    pub fn field_errors(&self) -> Vec<Option<String>> {
+        self.cipher()
+            .key_schema()
+            .iter()
+            .zip(&self.key_text_areas)
+            .map(|(field, text_area)| {
+                let value = text_area.lines().join(" ");
+                if value.trim().is_empty() && !self.show_validation {
+                    return None;
+                }
+                field.validate(&value).err()
+            })
+            .collect()
+    }
+
+    /// Collects the values typed into the input fields, runs the selected cipher and switches to
+    /// the result screen. If the cipher rejects the input the error is kept in [`App::error`] and
+    /// the user stays on the input screen to fix it.
+    fn submit_inputs(&mut self) {
+        self.show_validation = true;
+        if self.field_errors().iter().any(|error| error.is_some()) {
+            self.error = Some("Some of the keys are invalid".to_string());
+            return;
+        }
+        let schema = self.cipher().key_schema();
+        self.keys = schema
+            .iter()
+            .zip(&self.key_text_areas)
+            .map(|(field, text_area)| (field.id.to_string(), text_area.lines().join(" ")))
+            .collect();
+        debug!("Keys: {:?}", self.keys);
+        let mut input_text = self.input_text_area.lines().join(" ");
+        if self.read_from_file {
+            match fs::read_to_string(input_text.trim()) {
+                Ok(contents) => input_text = contents,
+                Err(e) => {
+                    self.error = Some(format!("Error reading file: {e}"));
+                    return;
+                }
+            }
+        }
+        debug!("{}", input_text.clone());
+        let output = match self.mode.selected_mode {
+            CipherMode::Encrypt => self.cipher().encrypt(&input_text, &self.keys),
+            CipherMode::Decrypt => self.cipher().decrypt(&input_text, &self.keys),
+        };
+        match output {
+            Ok(output) => {
+                match self.mode.selected_mode {
+                    CipherMode::Encrypt => {
+                        self.plaintext = input_text;
+                        self.encrypted_string = output;
+                    }
+                    CipherMode::Decrypt => {
+                        self.plaintext = output;
+                        self.encrypted_string = input_text;
+                    }
+                }
+                self.key_material = self.cipher().key_material(&self.keys);
+                self.error = None;
+                self.restore_spacing = false;
+                self.current_screen = CurrentScreen::SeeingResult;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
     /// Handles the key events and updates the state of [`App`].
     fn on_key_event(&mut self, key: KeyEvent) {
         if key.code == KeyCode::Esc {
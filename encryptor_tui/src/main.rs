@@ -1,11 +1,11 @@
 use app::App;
 use color_eyre::{
     config::HookBuilder,
-    eyre::{self, set_hook, Context},
+    eyre::{self, Context},
     Result,
 };
 use crossterm::{
-    event::{self, EnableMouseCapture, Event, KeyCode},
+    event::{self, Event},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
@@ -18,7 +18,7 @@ use ratatui::{
 use std::{
     fs::File,
     io::{self, stdout},
-    panic::{self, take_hook},
+    panic::{self},
     time::Duration,
 };
 use tracing::{debug, info, instrument, trace, Level};
@@ -36,20 +36,19 @@ fn main() -> color_eyre::Result<()> {
 
     init_error_hooks()?;
     // create app and run it
-    let mut app = App::new();
+    let app = App::new();
     let _guard = init_tracing()?;
     info!("Starting tracing example");
     let mut terminal = init_terminal()?;
     let mut events = vec![]; // a buffer to store the recent events to display in the UI
     handle_events(&mut events)?;
-    app.run(&mut terminal, &events);
+    app.run(&mut terminal, &events)?;
     restore_terminal()?;
     info!("Exiting tracing example");
     println!("See the tracing.log file for the logs");
     Ok(())
 }
 
-
 pub fn init_tui() -> io::Result<Terminal<impl Backend>> {
     enable_raw_mode()?;
     execute!(stdout(), EnterAlternateScreen)?;
@@ -62,11 +61,6 @@ pub fn restore_tui() -> io::Result<()> {
     Ok(())
 }
 
-fn should_exit(events: &[Event]) -> bool {
-    events
-        .iter()
-        .any(|event| matches!(event, Event::Key(key) if key.code == KeyCode::Char('q')))
-}
 /// Handle events and insert them into the events vector keeping only the last 10 events
 #[instrument(skip(events))]
 fn handle_events(events: &mut Vec<Event>) -> Result<()> {
@@ -83,7 +77,7 @@ fn handle_events(events: &mut Vec<Event>) -> Result<()> {
 fn ui(frame: &mut ratatui::Frame, events: &[Event]) {
     // To view this event, run the example with `RUST_LOG=tracing=debug cargo run --example tracing`
     trace!(frame_count = frame.count(), event_count = events.len());
-    let area = frame.size();
+    let area = frame.area();
     let events = events.iter().map(|e| format!("{e:?}")).collect::<Vec<_>>();
     let paragraph = Paragraph::new(events.join("\n"))
         .block(Block::bordered().title("Tracing example. Press 'q' to quit."));
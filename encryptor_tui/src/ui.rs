@@ -1,37 +1,16 @@
-use adfgvx_cipher::{decrypt_adfgvx, encrypt_adfgvx};
-use cli_log::debug;
-use color_eyre::eyre::bail;
-use crossterm::cursor::{Hide, Show};
-use crossterm::event::{read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent};
-use crossterm::execute;
-use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
-};
-use morse_code::{decrypt_morse_code, encrypt_morse_code};
-use ratatui::prelude::{CrosstermBackend, Stylize};
-use ratatui::Terminal;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{
-        palette::tailwind::{BLUE, GREEN, SLATE},
-        Color, Modifier, Style,
-    },
+    style::{palette::tailwind::SLATE, Color, Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
     Frame,
 };
-use std::fs;
-use std::io::{Result, Write};
-use tracing::field::debug;
-use tui_input::backend::crossterm as backend;
-use tui_input::backend::crossterm::EventHandler;
+use std::io::Result;
 use tui_textarea::TextArea;
-use tui_textarea::{Input, Key};
-use utils::{generate_keyword_string, remove_punctuation, remove_whitespace};
-use vigenere_cipher::{decrypt, generate_cipher};
+use utils::cipher::{key_value, CipherMode, KeyKind};
 const SELECTED_STYLE: Style = Style::new().bg(SLATE.c800).add_modifier(Modifier::BOLD);
 
-use crate::app::{App, CurrentScreen, EncryptionMethods, Modes, SelectedMode};
+use crate::app::{App, CurrentScreen, Inputs};
 
 pub fn ui(frame: &mut Frame, app: &mut App) -> Result<()> {
     let chunks = Layout::default()
@@ -67,7 +46,7 @@ pub fn ui(frame: &mut Frame, app: &mut App) -> Result<()> {
                 .encryption_methods_list
                 .items
                 .iter()
-                .map(|method| ListItem::from(method.name.clone()))
+                .map(|method| ListItem::from(format!("{} - {}", method.name, method.description)))
                 .collect();
             let list = List::new(items)
                 .block(Block::bordered().title("Choose the Method of Encryption"))
@@ -99,11 +78,7 @@ pub fn ui(frame: &mut Frame, app: &mut App) -> Result<()> {
                 .repeat_highlight_symbol(true);
 
             frame.render_stateful_widget(list, chunks[1], &mut state);
-            let mode = match app.encryption {
-                EncryptionMethods::VigenereCipher => "Using Vigenere Cipher",
-                EncryptionMethods::ADFGVX => "using ADFGVX Cipher",
-                EncryptionMethods::MorseCode => "Using Morse code",
-            };
+            let mode = format!("Using {}", app.cipher().name());
             let footer_string = format!(
                 "Created by Jayan Sunil github:JayanAXHF\n
             Press `Esc`, `Ctrl-C` or `q` to stop running. {}
@@ -119,18 +94,15 @@ pub fn ui(frame: &mut Frame, app: &mut App) -> Result<()> {
             frame.render_widget(footer, chunks[2]);
         }
         CurrentScreen::InputtingValues => {
-            let method = match app.encryption {
-                EncryptionMethods::VigenereCipher => "Vigenere Cipher",
-                EncryptionMethods::ADFGVX => "ADFGVX Cipher",
-                EncryptionMethods::MorseCode => "Morse code",
-            };
+            let method = app.cipher().name();
             let mode = match app.mode.selected_mode {
-                SelectedMode::Encrypt => "Encrypting",
-                SelectedMode::Decrypt => "Decrypting",
+                CipherMode::Encrypt => "Encrypting",
+                CipherMode::Decrypt => "Decrypting",
             };
             let footer_string = format!(
                 "Created by Jayan Sunil github:JayanAXHF\n
-            Press `Ctrl-C` or `q` to stop running or press `Ctrl-S` to continue. {} using {}
+            Press `Ctrl-C` or `q` to stop running or press `Ctrl-S` to continue. Use `←`/`→` to \
+            pick an option. {} using {}
 ",
                 mode, method
             );
@@ -139,265 +111,220 @@ pub fn ui(frame: &mut Frame, app: &mut App) -> Result<()> {
                 Style::default().fg(Color::Blue),
             ))
             .centered();
-            let mut keyword_text_area_block = Block::bordered().title("Keyword");
             let mut text_mode = String::new();
             match app.mode.selected_mode {
-                SelectedMode::Encrypt => {
+                CipherMode::Encrypt => {
                     text_mode.push_str("Encrypted Text");
                 }
-                SelectedMode::Decrypt => {
+                CipherMode::Decrypt => {
                     text_mode.push_str("Decrypted Text");
                 }
             }
             if app.read_from_file {
                 text_mode = String::from("Enter Filename")
             }
-            let mut input_text_area_block = Block::bordered().title(text_mode);
-            let mut column_key_text_area_block =
-                Block::bordered().title("Column Key[num. 1-6 separted by whitespace]");
-            match app.currently_editing {
-                crate::app::Inputs::Keyword => {
-                    keyword_text_area_block = keyword_text_area_block.border_style(Color::Green);
-                }
-                crate::app::Inputs::InputText => {
-                    input_text_area_block = input_text_area_block.border_style(Color::Green);
-                }
-                crate::app::Inputs::ColumnKey => {
-                    column_key_text_area_block =
-                        column_key_text_area_block.border_style(Color::Green);
-                }
-            }
-            match app.encryption {
-                EncryptionMethods::VigenereCipher => {
-                    let split_layout = Layout::new(
-                        Direction::Horizontal,
-                        vec![Constraint::Percentage(30), Constraint::Percentage(70)],
-                    )
-                    .split(chunks[1]);
-                    let mut textarea = app.keyword_text_area.clone();
-                    textarea.set_block(keyword_text_area_block);
-
-                    let mut input_text_area = app.input_text_area.clone();
-                    input_text_area.set_block(input_text_area_block);
-                    //textarea.input(app.keyword_input.clone());
-                    frame.render_widget(&textarea, split_layout[0]);
-                    frame.render_widget(&input_text_area, split_layout[1]);
-                }
-                EncryptionMethods::ADFGVX => {
-                    let split_layout = Layout::new(
-                        Direction::Horizontal,
-                        vec![Constraint::Percentage(30), Constraint::Percentage(70)],
-                    )
-                    .split(chunks[1]);
-                    let mut textarea = app.keyword_text_area.clone();
-                    let mut input_text_area = app.input_text_area.clone();
-                    let mut column_key_text_area = app.column_key_text_area.clone();
-                    textarea.set_block(keyword_text_area_block);
-                    input_text_area.set_block(input_text_area_block);
-                    column_key_text_area.set_block(column_key_text_area_block);
-                    let double_split = Layout::default()
-                        .direction(Direction::Vertical)
-                        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-                        .split(split_layout[0]);
-
-                    frame.render_widget(&input_text_area, split_layout[1]);
-                    //textarea.input(app.keyword_input.clone());
-                    frame.render_widget(&textarea, double_split[0]);
-                    frame.render_widget(&column_key_text_area, double_split[1]);
-                }
-                EncryptionMethods::MorseCode => {
-                    let mut textarea = app.input_text_area.clone();
-                    let mut text_mode = String::from("Enter the text to ");
-                    match app.mode.selected_mode {
-                        SelectedMode::Encrypt => {
-                            text_mode.push_str("encrypt");
-                        }
-                        SelectedMode::Decrypt => {
-                            text_mode.push_str("decrypt");
-                        }
-                    }
-                    textarea.set_block(input_text_area_block);
-                    frame.render_widget(&textarea, chunks[1]);
+            let key_text_areas = app.key_text_areas.clone();
+            let input_text_area = app.input_text_area.clone();
+            let errors = app.field_errors();
+            render_fields(
+                frame,
+                chunks[1],
+                app,
+                key_text_areas,
+                &errors,
+                input_text_area,
+                text_mode,
+            );
+            match &app.error {
+                Some(error) => {
+                    let error = Paragraph::new(Text::styled(
+                        format!("Error: {error}"),
+                        Style::default().fg(Color::Red),
+                    ))
+                    .centered();
+                    frame.render_widget(error, chunks[2]);
                 }
+                None => frame.render_widget(footer, chunks[2]),
             }
-            frame.render_widget(footer, chunks[2]);
         }
         CurrentScreen::SeeingResult => {
-            let method = match app.encryption {
-                EncryptionMethods::VigenereCipher => "Vigenere Cipher",
-                EncryptionMethods::ADFGVX => "ADFGVX Cipher",
-                EncryptionMethods::MorseCode => "Morse code",
-            };
+            let method = app.cipher().name();
             let mode = match app.mode.selected_mode {
-                SelectedMode::Encrypt => "Encrypting",
-                SelectedMode::Decrypt => "Decrypting",
+                CipherMode::Encrypt => "Encrypting",
+                CipherMode::Decrypt => "Decrypting",
+            };
+            let spacing_hint = match app.mode.selected_mode {
+                CipherMode::Encrypt => "",
+                CipherMode::Decrypt if app.restore_spacing => " Press `Ctrl-R` to hide spacing.",
+                CipherMode::Decrypt => " Press `Ctrl-R` to restore spacing.",
             };
             let footer_string = format!(
                 "Created by Jayan Sunil github:JayanAXHF. Showing Results\n
-            Press `Ctrl-C` or `q` to stop running or press `Ctrl-S` to continue. {} using {}
+            Press `Ctrl-C` or `q` to stop running or press `Ctrl-S` to continue.{} {} using {}
 ",
-                mode, method
+                spacing_hint, mode, method
             );
             let footer = Paragraph::new(Text::styled(
                 footer_string,
                 Style::default().fg(Color::Blue),
             ))
             .centered();
-            let mut keyword_text_area_block = Block::bordered().title("Keyword");
-            let mut text_mode = String::new();
-            match app.mode.selected_mode {
-                SelectedMode::Encrypt => {
-                    text_mode.push_str("Encrypted Text");
-                }
-                SelectedMode::Decrypt => {
-                    text_mode.push_str("Decrypted Text");
-                }
-            }
-            if app.read_from_file {
-                text_mode = String::from("Enter Filename")
+            let text_mode = match app.mode.selected_mode {
+                CipherMode::Encrypt => "Encrypted Text",
+                CipherMode::Decrypt => "Decrypted Text",
+            };
+            let output_text = app.result_text();
+            let key_text_areas = app
+                .cipher()
+                .key_schema()
+                .iter()
+                .map(|field| TextArea::new(vec![key_value(&app.keys, field.id).to_string()]))
+                .collect();
+            let input_text_area = TextArea::new(
+                output_text
+                    .chars()
+                    .collect::<Vec<char>>()
+                    .chunks(100)
+                    .map(|chunk| chunk.iter().collect())
+                    .collect(),
+            );
+            let mut area = chunks[1];
+            // Tables too wide to leave room for the result, like a full tableau, are left out.
+            let material = app.key_material.as_ref().map(|material| {
+                let width = material
+                    .lines()
+                    .map(|line| line.chars().count())
+                    .max()
+                    .unwrap_or(0) as u16
+                    + 4;
+                (material, width)
+            });
+            if let Some((material, width)) = material.filter(|&(_, width)| width <= area.width / 2)
+            {
+                let layout = Layout::new(
+                    Direction::Horizontal,
+                    [Constraint::Min(1), Constraint::Length(width)],
+                )
+                .split(area);
+                area = layout[0];
+                let material = Paragraph::new(material.as_str())
+                    .block(Block::bordered().title("Key Material"));
+                frame.render_widget(material, layout[1]);
             }
-            let mut input_text_area_block = Block::bordered().title(text_mode);
-            let mut column_key_text_area_block =
-                Block::bordered().title("Column Key[num. 1-6 separated by whitespace]");
-            match app.currently_editing {
-                crate::app::Inputs::Keyword => {
-                    keyword_text_area_block = keyword_text_area_block.border_style(Color::Green);
-                }
-                crate::app::Inputs::InputText => {
-                    input_text_area_block = input_text_area_block.border_style(Color::Green);
-                }
-                crate::app::Inputs::ColumnKey => {
-                    column_key_text_area_block =
-                        column_key_text_area_block.border_style(Color::Green);
+            render_fields(
+                frame,
+                area,
+                app,
+                key_text_areas,
+                &[],
+                input_text_area,
+                text_mode.to_string(),
+            );
+            match &app.error {
+                Some(error) => {
+                    let error = Paragraph::new(Text::styled(
+                        format!("Error: {error}"),
+                        Style::default().fg(Color::Red),
+                    ))
+                    .centered();
+                    frame.render_widget(error, chunks[2]);
                 }
+                None => frame.render_widget(footer, chunks[2]),
             }
-            app.plaintext = remove_punctuation(&remove_whitespace(&mut app.plaintext.clone()));
-            app.encrypted_string =
-                remove_punctuation(&remove_whitespace(&mut app.encrypted_string.clone()));
-            match app.encryption {
-                EncryptionMethods::VigenereCipher => {
-                    debug!(
-                        "data: {:#?} ---- {:#?} --- {:#?} --- {:#?} --- {:?}",
-                        app.keyword.clone(),
-                        app.plaintext.clone(),
-                        app.encrypted_string.clone(),
-                        generate_keyword_string(&mut app.keyword, app.encrypted_string.len()).len(),
-                        app.encrypted_string.len()
-                    );
-
-                    let output_text = match app.mode.selected_mode {
-                        SelectedMode::Encrypt => generate_cipher(
-                            app.plaintext.clone(),
-                            generate_keyword_string(&mut app.keyword.clone(), app.plaintext.len()),
-                        ),
-                        SelectedMode::Decrypt => {
-                            decrypt(app.encrypted_string.clone(), app.keyword.clone(), false)
-                        }
-                    };
-                    match app.mode.selected_mode {
-                        SelectedMode::Encrypt => {
-                            app.encrypted_string = output_text.clone();
-                        }
-                        SelectedMode::Decrypt => {
-                            app.plaintext = output_text.clone();
-                        }
-                    }
-                    let split_layout = Layout::new(
-                        Direction::Horizontal,
-                        vec![Constraint::Percentage(30), Constraint::Percentage(70)],
-                    )
-                    .split(chunks[1]);
-                    let mut textarea = TextArea::new(vec![app.keyword.clone()]);
-                    textarea.set_block(keyword_text_area_block);
-
-                    let mut input_text_area = TextArea::new(
-                        output_text
-                            .chars()
-                            .collect::<Vec<char>>()
-                            .chunks(100)
-                            .map(|chunk| chunk.iter().collect())
-                            .collect(),
-                    );
-                    input_text_area.set_block(input_text_area_block);
-                    //textarea.input(app.keyword_input.clone());
-                    frame.render_widget(&textarea, split_layout[0]);
-                    frame.render_widget(&input_text_area, split_layout[1]);
-                }
-                EncryptionMethods::ADFGVX => {
-                    let split_layout = Layout::new(
-                        Direction::Horizontal,
-                        vec![Constraint::Percentage(30), Constraint::Percentage(70)],
-                    )
-                    .split(chunks[1]);
-                    let column_key = app.column_key.clone();
-                    debug!("{:?} -- Column Key", column_key);
-
-                    let output_text = match app.mode.selected_mode {
-                        SelectedMode::Encrypt => encrypt_adfgvx(
-                            app.plaintext.clone(),
-                            app.keyword.clone(),
-                            app.column_key.clone(),
-                        ),
-                        SelectedMode::Decrypt => decrypt_adfgvx(
-                            app.encrypted_string.clone(),
-                            app.keyword.clone(),
-                            app.column_key.clone(),
-                        ),
-                    };
-                    match app.mode.selected_mode {
-                        SelectedMode::Encrypt => {
-                            app.encrypted_string = output_text.clone();
-                        }
-                        SelectedMode::Decrypt => app.plaintext = output_text.clone(),
-                    }
-                    let mut textarea = TextArea::new(vec![app.keyword.clone()]);
-                    textarea.set_block(keyword_text_area_block);
-                    let mut input_text_area = TextArea::new(vec![output_text]);
+        }
+        _ => {}
+    }
+    Ok(())
+}
 
-                    let mut column_key_text_area =
-                        TextArea::from(app.column_key.iter().map(|f| f.to_string()));
+/// Every option of a choice field in a row, with the selected one highlighted.
+fn choice_line(options: &[&'static str], selected: &str) -> Line<'static> {
+    let spans = options.iter().flat_map(|option| {
+        let style = match option.eq_ignore_ascii_case(selected.trim()) {
+            true => SELECTED_STYLE.fg(Color::Green),
+            false => Style::default().fg(Color::DarkGray),
+        };
+        [Span::styled(*option, style), Span::raw(" ")]
+    });
+    Line::from(spans.collect::<Vec<_>>())
+}
 
-                    input_text_area.set_block(input_text_area_block);
-                    column_key_text_area.set_block(column_key_text_area_block);
-                    let double_split = Layout::default()
-                        .direction(Direction::Vertical)
-                        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-                        .split(split_layout[0]);
+/// Lays out one text area per key field of the selected cipher on the left and the main text area
+/// on the right, highlighting whichever one is being edited. A field with an entry in `errors`
+/// gets a red border and the message underneath it. Choice fields list their options instead,
+/// such as the Morse table or the Vigenère alphabet.
+fn render_fields(
+    frame: &mut Frame,
+    area: Rect,
+    app: &App,
+    mut key_text_areas: Vec<TextArea>,
+    errors: &[Option<String>],
+    mut input_text_area: TextArea,
+    input_title: String,
+) {
+    let mut input_text_area_block = Block::bordered().title(input_title);
+    if app.currently_editing == Inputs::InputText {
+        input_text_area_block = input_text_area_block.border_style(Color::Green);
+    }
+    input_text_area.set_block(input_text_area_block);
 
-                    frame.render_widget(&input_text_area, split_layout[1]);
-                    //textarea.input(app.keyword_input.clone());
-                    frame.render_widget(&textarea, double_split[0]);
-                    frame.render_widget(&column_key_text_area, double_split[1]);
-                }
-                EncryptionMethods::MorseCode => {
-                    let mut textarea = app.input_text_area.clone();
-                    let mut text_mode = String::from("Enter the text to ");
-                    let output_text = match app.mode.selected_mode {
-                        SelectedMode::Encrypt => {
-                            text_mode.push_str("encrypt");
-                            encrypt_morse_code(app.plaintext.clone())
-                        }
-                        SelectedMode::Decrypt => {
-                            text_mode.push_str("decrypt");
-                            decrypt_morse_code(app.encrypted_string.clone())
-                        }
-                    };
+    if key_text_areas.is_empty() {
+        frame.render_widget(&input_text_area, area);
+        return;
+    }
 
-                    match app.mode.selected_mode {
-                        SelectedMode::Encrypt => {
-                            app.encrypted_string = output_text.clone();
-                        }
-                        SelectedMode::Decrypt => {
-                            app.plaintext = output_text.clone();
-                        }
-                    }
-                    textarea.set_block(input_text_area_block);
-                    frame.render_widget(&textarea, chunks[1]);
-                }
+    let split_layout = Layout::new(
+        Direction::Horizontal,
+        vec![Constraint::Percentage(30), Constraint::Percentage(70)],
+    )
+    .split(area);
+    let key_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Ratio(1, key_text_areas.len() as u32);
+            key_text_areas.len()
+        ])
+        .split(split_layout[0]);
+    for (index, (field, text_area)) in app
+        .cipher()
+        .key_schema()
+        .iter()
+        .zip(key_text_areas.iter_mut())
+        .enumerate()
+    {
+        let title = match (&field.kind, field.hint()) {
+            (KeyKind::Choice { .. }, _) => format!("{} [←/→]", field.label),
+            (_, Some(hint)) => format!("{} [{}]", field.label, hint),
+            (_, None) => field.label.to_string(),
+        };
+        let mut block = Block::bordered().title(title);
+        if app.currently_editing == Inputs::Key(index) {
+            block = block.border_style(Color::Green);
+        }
+        let error = errors.get(index).cloned().flatten();
+        if error.is_some() {
+            block = block.border_style(Color::Red);
+        }
+        let field_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(1)])
+            .split(key_layout[index]);
+        match field.kind {
+            KeyKind::Choice { options } => {
+                let selector = Paragraph::new(choice_line(options, &text_area.lines().join(" ")))
+                    .wrap(Wrap { trim: true })
+                    .block(block);
+                frame.render_widget(selector, field_layout[0]);
+            }
+            _ => {
+                text_area.set_block(block);
+                frame.render_widget(&*text_area, field_layout[0]);
             }
-            frame.render_widget(footer, chunks[2]);
         }
-        _ => {}
+        if let Some(error) = error {
+            let error = Paragraph::new(Text::styled(error, Style::default().fg(Color::Red)));
+            frame.render_widget(error, field_layout[1]);
+        }
     }
-    Ok(())
+    frame.render_widget(&input_text_area, split_layout[1]);
 }
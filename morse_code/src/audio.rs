@@ -0,0 +1,263 @@
+//! Turning Morse code into sound: a sine tone keyed on and off with soft edges, written out as a
+//! WAV file.
+use std::f64::consts::PI;
+use std::io::{self, Read, Write};
+
+use utils::error::CipherError;
+
+use crate::timing::{Spacing, Timing};
+
+/// How the Morse code sounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioSettings {
+    /// Speed of the characters in words per minute, using PARIS as the standard word.
+    pub wpm: f64,
+    /// Overall speed when lower than `wpm`: the characters keep their speed and the gaps between
+    /// them are stretched (Farnsworth timing).
+    pub farnsworth_wpm: Option<f64>,
+    /// Pitch of the tone in Hz.
+    pub frequency: f64,
+    pub sample_rate: u32,
+    /// Time the tone takes to fade in and out, in seconds, so that it does not click.
+    pub rise_time: f64,
+    /// Peak level between 0 and 1.
+    pub amplitude: f64,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            wpm: 20.0,
+            farnsworth_wpm: None,
+            frequency: 600.0,
+            sample_rate: 44_100,
+            rise_time: 0.005,
+            amplitude: 0.8,
+        }
+    }
+}
+
+impl AudioSettings {
+    /// Checks that every setting is in a range that gives audible Morse.
+    pub fn validate(&self) -> Result<(), CipherError> {
+        let invalid = |reason: &str| Err(CipherError::InvalidKey(reason.to_string()));
+        if !(self.wpm > 0.0 && self.wpm.is_finite()) {
+            return invalid("the speed must be above 0 wpm");
+        }
+        if let Some(farnsworth_wpm) = self.farnsworth_wpm {
+            if !(farnsworth_wpm > 0.0 && farnsworth_wpm <= self.wpm) {
+                return invalid("the Farnsworth speed must be above 0 and at most the speed");
+            }
+        }
+        if self.sample_rate < 1000 {
+            return invalid("the sample rate must be at least 1000 Hz");
+        }
+        if !(self.frequency > 0.0 && self.frequency < self.sample_rate as f64 / 2.0) {
+            return invalid("the tone must be above 0 Hz and below half the sample rate");
+        }
+        if !(0.0..=1.0).contains(&self.amplitude) || !(0.0..).contains(&self.rise_time) {
+            return invalid("the amplitude must be between 0 and 1 and the rise time positive");
+        }
+        Ok(())
+    }
+
+    /// Length of a dit in seconds.
+    pub fn dit(&self) -> f64 {
+        1.2 / self.wpm
+    }
+
+    /// The gaps these settings key with, stretched when `farnsworth_wpm` is set.
+    pub fn spacing(&self) -> Spacing {
+        match self.farnsworth_wpm {
+            Some(overall_wpm) => Spacing::farnsworth(self.wpm, overall_wpm),
+            None => Spacing::default(),
+        }
+    }
+}
+
+/// Renders Morse code as produced by [`crate::encrypt_morse_code`] to 16 bit PCM samples.
+pub fn synthesize(morse: &str, settings: &AudioSettings) -> Result<Vec<i16>, CipherError> {
+    render(&Timing::from_morse(morse, settings.spacing())?, settings)
+}
+
+/// Renders keyed Morse code to 16 bit PCM samples. The gaps are taken from `timing`, the speed
+/// and sound from `settings`.
+pub fn render(timing: &Timing, settings: &AudioSettings) -> Result<Vec<i16>, CipherError> {
+    settings.validate()?;
+    let rate = settings.sample_rate as f64;
+    let mut samples = Vec::new();
+    for (on, seconds) in timing.seconds(settings.dit()) {
+        let length = (seconds * rate).round() as usize;
+        if !on {
+            samples.extend(std::iter::repeat_n(0, length));
+            continue;
+        }
+        let rise = ((settings.rise_time * rate) as usize).min(length / 2);
+        for i in 0..length {
+            // Raised cosine edges: 0 at the very start and end, 1 in between.
+            let envelope = match i.min(length - 1 - i) {
+                edge if edge < rise => 0.5 * (1.0 - (PI * edge as f64 / rise as f64).cos()),
+                _ => 1.0,
+            };
+            let tone = (2.0 * PI * settings.frequency * i as f64 / rate).sin();
+            samples.push((tone * envelope * settings.amplitude * i16::MAX as f64).round() as i16);
+        }
+    }
+    Ok(samples)
+}
+
+/// Writes `samples` as a mono 16 bit PCM WAV file.
+pub fn write_wav(mut writer: impl Write, samples: &[i16], sample_rate: u32) -> io::Result<()> {
+    let data_length = samples.len() as u32 * 2;
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_length).to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16_u32.to_le_bytes())?;
+    // PCM, one channel
+    writer.write_all(&1_u16.to_le_bytes())?;
+    writer.write_all(&1_u16.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * 2).to_le_bytes())?;
+    // Bytes per frame and bits per sample
+    writer.write_all(&2_u16.to_le_bytes())?;
+    writer.write_all(&16_u16.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_length.to_le_bytes())?;
+    for sample in samples {
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+/// Reads a mono WAV file with 8, 16, 24 or 32 bit PCM or 32 bit float samples. Returns the
+/// samples scaled to -1..1 and the sample rate.
+pub fn read_wav(mut reader: impl Read) -> io::Result<(Vec<f32>, u32)> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason.to_string());
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(invalid("not a WAV file"));
+    }
+    let u16_at = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+    let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+
+    // (format, channels, sample rate, bits per sample)
+    let mut format = None;
+    let mut data = None;
+    let mut at = 12;
+    while at + 8 <= bytes.len() {
+        let length = u32_at(at + 4) as usize;
+        let body = at + 8..(at + 8 + length).min(bytes.len());
+        match &bytes[at..at + 4] {
+            b"fmt " if body.len() >= 16 => {
+                let start = body.start;
+                format = Some((
+                    u16_at(start),
+                    u16_at(start + 2),
+                    u32_at(start + 4),
+                    u16_at(start + 14),
+                ));
+            }
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        // Chunks are padded to an even length.
+        at += 8 + length + length % 2;
+    }
+    let (Some((format, channels, sample_rate, bits)), Some(data)) = (format, data) else {
+        return Err(invalid("the WAV file has no format or no data"));
+    };
+    if channels != 1 {
+        return Err(invalid("only mono WAV files are supported"));
+    }
+    // 0xFFFE is WAVE_FORMAT_EXTENSIBLE, which tools also use for plain PCM.
+    let float = match (format, bits) {
+        (1 | 0xFFFE, 8 | 16 | 24 | 32) => false,
+        (3, 32) => true,
+        _ => return Err(invalid("only PCM and 32 bit float WAV files are supported")),
+    };
+    let width = bits as usize / 8;
+    let samples = bytes[data]
+        .chunks_exact(width)
+        .map(|sample| match (width, float) {
+            (1, _) => (sample[0] as f32 - 128.0) / 128.0,
+            (2, _) => i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32_768.0,
+            (3, _) => {
+                i32::from_le_bytes([0, sample[0], sample[1], sample[2]]) as f32 / 2_147_483_648.0
+            }
+            (_, false) => i32::from_le_bytes(sample.try_into().unwrap()) as f32 / 2_147_483_648.0,
+            (_, true) => f32::from_le_bytes(sample.try_into().unwrap()),
+        })
+        .collect();
+    Ok((samples, sample_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timing() {
+        let settings = AudioSettings::default();
+        assert!((settings.dit() - 0.06).abs() < 1e-12);
+        // E, a space between letters, then E again after a word break.
+        let samples = synthesize(". . / .", &settings).unwrap();
+        let dit = 2646;
+        assert_eq!(3 * dit + 3 * dit + 7 * dit, samples.len());
+        assert_eq!(0, samples[0]);
+        assert!(samples[dit / 2..dit].iter().any(|&s| s > 20_000));
+        assert!(samples[dit..4 * dit].iter().all(|&s| s == 0));
+        assert_eq!(4 * dit, synthesize("-", &settings).unwrap().len() + dit);
+    }
+
+    #[test]
+    fn test_farnsworth() {
+        let settings = AudioSettings {
+            wpm: 20.0,
+            farnsworth_wpm: Some(10.0),
+            ..Default::default()
+        };
+        let word_gap = settings.spacing().word * settings.dit();
+        // PARIS plus the gap after it takes 6 seconds at 10 wpm.
+        let paris = ".--. .- .-. .. ...";
+        let seconds = synthesize(paris, &settings).unwrap().len() as f64 / 44_100.0 + word_gap;
+        assert!((seconds - 6.0).abs() < 0.01, "{seconds}");
+        assert!(AudioSettings {
+            farnsworth_wpm: Some(30.0),
+            ..settings
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_write_wav() {
+        let mut wav = Vec::new();
+        write_wav(&mut wav, &[0, 1, -1], 8000).unwrap();
+        assert_eq!(44 + 6, wav.len());
+        assert_eq!(b"RIFF", &wav[..4]);
+        assert_eq!(42, u32::from_le_bytes(wav[4..8].try_into().unwrap()));
+        assert_eq!(b"WAVEfmt ", &wav[8..16]);
+        assert_eq!(8000, u32::from_le_bytes(wav[24..28].try_into().unwrap()));
+        assert_eq!(b"data", &wav[36..40]);
+        assert_eq!([0xff, 0xff], wav[48..50]);
+
+        let (samples, sample_rate) = read_wav(wav.as_slice()).unwrap();
+        assert_eq!(8000, sample_rate);
+        assert_eq!(vec![0.0, 1.0 / 32_768.0, -1.0 / 32_768.0], samples);
+        // Stereo
+        wav[22] = 2;
+        assert!(read_wav(wav.as_slice()).is_err());
+        assert!(read_wav(&b"RIFF"[..]).is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(synthesize("..x", &AudioSettings::default()).is_err());
+        let settings = AudioSettings {
+            frequency: 30_000.0,
+            ..Default::default()
+        };
+        assert!(synthesize(".", &settings).is_err());
+    }
+}
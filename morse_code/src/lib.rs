@@ -1,104 +1,746 @@
-use std::collections::HashMap;
+pub mod audio;
+pub mod timing;
+pub mod transcribe;
 
-pub fn encrypt_morse_code(plaintext: String) -> String {
-    let words = plaintext.split_whitespace();
+pub mod tables;
+
+use tables::{join_voicing, Table, SHIFT_IN, SHIFT_OUT};
+use utils::cipher::{key_value, Cipher, CipherMode, KeyField, KeyKind, Keys};
+use utils::error::CipherError;
+use utils::stream::StreamCipher;
+
+/// What to do with text that has no Morse code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Unmappable {
+    /// Fail, pointing at the first character or code group that cannot be converted.
+    #[default]
+    Error,
+    /// Leave it out.
+    Skip,
+    /// Replace it with a question mark.
+    Substitute,
+}
+
+impl Unmappable {
+    /// The names accepted by [`Unmappable::from_name`], the default first.
+    pub const NAMES: [&'static str; 3] = ["error", "skip", "substitute"];
+
+    pub fn from_name(name: &str) -> Option<Unmappable> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "error" => Some(Unmappable::Error),
+            "skip" => Some(Unmappable::Skip),
+            "substitute" | "?" => Some(Unmappable::Substitute),
+            _ => None,
+        }
+    }
+}
+
+/// Procedural signals, sent as one character without the gaps between their letters and written
+/// in angle brackets, e.g. `<SK>`. AR, AS, BT and KN share their code with `+`, `&`, `=` and `(`
+/// and are read back as those characters.
+pub const PROSIGNS: [(&str, &str); 6] = [
+    ("AR", ".-.-."),
+    ("AS", ".-..."),
+    ("BT", "-...-"),
+    ("KN", "-.--."),
+    ("SK", "...-.-"),
+    ("SOS", "...---..."),
+];
+
+pub fn encrypt_morse_code(plaintext: String) -> Result<String, CipherError> {
+    encode(&plaintext, Table::Latin, Unmappable::Error)
+}
+
+pub fn decrypt_morse_code(encrypted_string: String) -> Result<String, CipherError> {
+    decode(&encrypted_string, Table::Latin, Unmappable::Error)
+}
+
+/// Converts text to Morse code, with letters separated by spaces and words by ` / `.
+///
+/// Sending starts in `table`. Letters that are only in the other table of the pair, Latin and
+/// [`Table::foreign`], are sent after a [`SHIFT_IN`] or [`SHIFT_OUT`] group that switches to it;
+/// the switches can also be written out as `<DO>` and `<SN>`.
+pub fn encode(
+    plaintext: &str,
+    table: Table,
+    unmappable: Unmappable,
+) -> Result<String, CipherError> {
+    let mut encoder = Encoder::new(table, unmappable);
     let mut encrypted_string = String::new();
-    let morse_code = morse_code_map();
-    for (i, word) in words.enumerate() {
-        if i != 0 {
-            encrypted_string.push_str(" / ");
+    encoder.update(plaintext, &mut encrypted_string)?;
+    encoder.finish(&mut encrypted_string)?;
+    Ok(encrypted_string)
+}
+
+/// The longest name written in angle brackets, `SOS`.
+const LONGEST_NAME: usize = 3;
+
+/// [`encode`] for text fed in pieces.
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    table: Table,
+    unmappable: Unmappable,
+    current: Table,
+    first_word: bool,
+    in_word: bool,
+    /// Characters read so far.
+    position: usize,
+    /// A `<` near the end of the input so far, with what follows it, that could still turn out to
+    /// start a prosign or table switch.
+    pending: Vec<char>,
+}
+
+impl Encoder {
+    pub fn new(table: Table, unmappable: Unmappable) -> Self {
+        Encoder {
+            table,
+            unmappable,
+            current: table,
+            first_word: true,
+            in_word: false,
+            position: 0,
+            pending: Vec::new(),
         }
-        for char in word.chars() {
-            let morse_letter = morse_code.get(&char.to_ascii_uppercase()).unwrap();
-            encrypted_string.push_str(morse_letter);
-            encrypted_string.push(' ');
+    }
+
+    /// Encodes `chars`, which follow the characters already read. Unless `last` is set, an
+    /// unfinished `<...>` at the end is kept for the next call.
+    fn encode(
+        &mut self,
+        chars: &[char],
+        last: bool,
+        encrypted_string: &mut String,
+    ) -> Result<(), CipherError> {
+        let mut i = 0;
+        while i < chars.len() {
+            let char = chars[i];
+            if char.is_whitespace() {
+                self.in_word = false;
+                i += 1;
+                continue;
+            }
+            let rest = &chars[i..];
+            if !last && char == '<' && rest.len() < LONGEST_NAME + 2 && !rest.contains(&'>') {
+                self.pending = rest.to_vec();
+                break;
+            }
+            let (codes, length) = match lookup(rest, &mut self.current, self.table) {
+                Some(found) => found,
+                None => match self.unmappable {
+                    Unmappable::Error => {
+                        return Err(CipherError::UnsupportedCharacter {
+                            character: char,
+                            position: self.position + i,
+                        })
+                    }
+                    Unmappable::Skip => {
+                        i += 1;
+                        continue;
+                    }
+                    Unmappable::Substitute => {
+                        let codes = self
+                            .current
+                            .encode('?')
+                            .or_else(|| Table::Latin.encode('?'))
+                            .ok_or(CipherError::UnsupportedCharacter {
+                                character: char,
+                                position: self.position + i,
+                            })?;
+                        (codes, 1)
+                    }
+                },
+            };
+            if !self.in_word {
+                if !self.first_word {
+                    encrypted_string.push_str(" / ");
+                }
+                self.first_word = false;
+                self.in_word = true;
+            }
+            for morse_letter in codes {
+                encrypted_string.push_str(morse_letter);
+                encrypted_string.push(' ');
+            }
+            i += length;
         }
+        self.position += i;
+        Ok(())
     }
-    encrypted_string
 }
 
-pub fn decrypt_morse_code(encrypted_string: String) -> String{
-    let morse_code = morse_code_map();
-    let words = encrypted_string.split(" / ");
-    let mut plaintext = String::new();
-    for word in words {
-        for char in word.split_whitespace() {
-            let normal_char = morse_code
+impl StreamCipher for Encoder {
+    fn update(&mut self, input: &str, output: &mut String) -> Result<(), CipherError> {
+        let mut chars = std::mem::take(&mut self.pending);
+        chars.extend(input.chars());
+        self.encode(&chars, false, output)
+    }
+
+    fn finish(&mut self, output: &mut String) -> Result<(), CipherError> {
+        let chars = std::mem::take(&mut self.pending);
+        self.encode(&chars, true, output)
+    }
+}
+
+/// The codes for the prosign, table switch or character at the start of `chars`, and how many
+/// characters it takes up. Switches to the other table of `table` when only that one has the
+/// character.
+fn lookup(chars: &[char], current: &mut Table, table: Table) -> Option<(Vec<&'static str>, usize)> {
+    if chars[0] == '<' {
+        let end = chars
+            .iter()
+            .take(LONGEST_NAME + 2)
+            .position(|&c| c == '>')?;
+        let name = chars[1..end]
+            .iter()
+            .collect::<String>()
+            .to_ascii_uppercase();
+        let code = match name.as_str() {
+            "DO" => {
+                *current = table.foreign();
+                SHIFT_IN
+            }
+            "SN" => {
+                *current = Table::Latin;
+                SHIFT_OUT
+            }
+            _ => PROSIGNS
                 .iter()
-                .find_map(|(key, &val)| if val == char { Some(key) } else { None });
-            match normal_char {
-                    Some(x) => plaintext.push(*x),
+                .find(|(prosign, _)| *prosign == name)
+                .map(|(_, code)| *code)?,
+        };
+        return Some((vec![code], end + 1));
+    }
+    if let Some(codes) = current.encode(chars[0]) {
+        return Some((codes, 1));
+    }
+    let (other, shift) = match current {
+        Table::Latin => (table.foreign(), SHIFT_IN),
+        _ => (Table::Latin, SHIFT_OUT),
+    };
+    let mut codes = other.encode(chars[0])?;
+    codes.insert(0, shift);
+    *current = other;
+    Some((codes, 1))
+}
+
+/// A code group that is not in the table, at the character position where it starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownGroup {
+    pub code: String,
+    pub position: usize,
+}
+
+/// Text read from Morse code, with everything that could not be read.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Decoded {
+    pub text: String,
+    pub unknown: Vec<UnknownGroup>,
+}
+
+/// A code group of Morse text, with dots and dashes in their plain form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Group {
+    pub code: String,
+    pub position: usize,
+    /// Whether a word break comes before the group.
+    pub new_word: bool,
+}
+
+/// Splits Morse text into code groups, accepting the ways it is commonly written down: `·`, `•`
+/// and `∙` for dots, `_`, `−`, `–` and `—` for dashes, `/` or `|` between words, and also a line
+/// break or three or more spaces. Anything else is kept in its group so it can be reported.
+pub(crate) fn groups(morse: &str) -> Vec<Group> {
+    let mut grouper = Grouper::default();
+    let mut groups: Vec<Group> = morse.chars().filter_map(|c| grouper.push(c)).collect();
+    groups.extend(grouper.finish());
+    groups
+}
+
+/// [`groups`] a character at a time.
+#[derive(Debug, Clone, Default)]
+struct Grouper {
+    current: Option<Group>,
+    new_word: bool,
+    spaces: usize,
+    position: usize,
+    started: bool,
+}
+
+impl Grouper {
+    /// Reads the next character, returning the group it ends if any.
+    fn push(&mut self, c: char) -> Option<Group> {
+        let position = self.position;
+        self.position += 1;
+        let symbol = match c {
+            '.' | '·' | '•' | '∙' => Some('.'),
+            '-' | '_' | '−' | '–' | '—' => Some('-'),
+            '/' | '|' => None,
+            c if c.is_whitespace() => None,
+            c => Some(c),
+        };
+        match symbol {
+            Some(symbol) => {
+                self.spaces = 0;
+                let group = self.current.get_or_insert_with(|| Group {
+                    code: String::new(),
+                    position,
+                    new_word: std::mem::take(&mut self.new_word) && self.started,
+                });
+                group.code.push(symbol);
+                self.started = true;
+                None
+            }
+            None => {
+                self.spaces = if c == ' ' { self.spaces + 1 } else { 0 };
+                if matches!(c, '/' | '|' | '\n') || self.spaces >= 3 {
+                    self.new_word = true;
+                }
+                self.current.take()
+            }
+        }
+    }
 
-                    None => println!("There is some error with the morse code map")
+    /// The group at the end of the text, if any.
+    fn finish(&mut self) -> Option<Group> {
+        self.current.take()
+    }
+}
+
+/// Converts Morse code to text, starting in `table` and following the [`SHIFT_IN`] and
+/// [`SHIFT_OUT`] groups. Letters are separated by whitespace and words as described for
+/// [`decode_report`].
+pub fn decode(
+    encrypted_string: &str,
+    table: Table,
+    unmappable: Unmappable,
+) -> Result<String, CipherError> {
+    let mut decoder = Decoder::new(table, unmappable);
+    let mut plaintext = String::new();
+    decoder.update(encrypted_string, &mut plaintext)?;
+    decoder.finish(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+/// Converts Morse code to text like [`decode`], but never fails: every code group that cannot be
+/// read is listed with its position, and replaced by `?` in the text unless `unmappable` says to
+/// skip it. See [`groups`] for the accepted separators and glyphs.
+pub fn decode_report(encrypted_string: &str, table: Table, unmappable: Unmappable) -> Decoded {
+    let mut decoder = Decoder::new(table, unmappable);
+    let mut decoded = Decoded::default();
+    for group in groups(encrypted_string) {
+        decoded
+            .unknown
+            .extend(decoder.decode(group, &mut decoded.text));
+    }
+    decoder.end(&mut decoded.text);
+    decoded
+}
+
+/// [`decode`] for Morse code fed in pieces.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    table: Table,
+    unmappable: Unmappable,
+    current: Table,
+    grouper: Grouper,
+    /// The last character read, held back in case a voicing mark that joins with it follows.
+    last: Option<char>,
+    /// Whether a word break was read and its space is still to be written, which waits for the
+    /// next character so that a word of nothing but a table switch adds no space.
+    space: bool,
+}
+
+impl Decoder {
+    pub fn new(table: Table, unmappable: Unmappable) -> Self {
+        Decoder {
+            table,
+            unmappable,
+            current: table,
+            grouper: Grouper::default(),
+            last: None,
+            space: false,
+        }
+    }
+
+    /// Writes out the text of `group`, returning it if it could not be read.
+    fn decode(&mut self, group: Group, plaintext: &mut String) -> Option<UnknownGroup> {
+        if group.new_word {
+            plaintext.extend(self.last.take());
+            self.space = true;
+        }
+        match group.code.as_str() {
+            SHIFT_IN => {
+                self.current = self.table.foreign();
+                return None;
+            }
+            SHIFT_OUT => {
+                self.current = Table::Latin;
+                return None;
             }
+            _ => {}
         }
+        if std::mem::take(&mut self.space) {
+            plaintext.push(' ');
+        }
+        let normal_char = self.current.decode(&group.code);
+        let prosign = PROSIGNS.iter().find(|(_, code)| *code == group.code);
+        match (normal_char, prosign) {
+            (Some(x), _) => {
+                let voiced = self.last.and_then(|kana| join_voicing(kana, x));
+                if let Some(voiced) = voiced.filter(|_| self.current == Table::Wabun) {
+                    self.last = Some(voiced);
+                } else {
+                    plaintext.extend(self.last.replace(x));
+                }
+                None
+            }
+            (None, Some((name, _))) => {
+                plaintext.extend(self.last.take());
+                plaintext.push_str(&format!("<{name}>"));
+                None
+            }
+            (None, None) => {
+                plaintext.extend(self.last.take());
+                if self.unmappable != Unmappable::Skip {
+                    plaintext.push('?');
+                }
+                Some(UnknownGroup {
+                    code: group.code,
+                    position: group.position,
+                })
+            }
+        }
+    }
+
+    /// Writes out what was held back and the space that ends the text.
+    fn end(&mut self, plaintext: &mut String) {
+        plaintext.extend(self.last.take());
         plaintext.push(' ');
     }
-   plaintext
-}
-
-fn morse_code_map() -> HashMap<char, &'static str> {
-    let mut morse_map = HashMap::new();
-
-    // Letters
-    morse_map.insert('A', ".-");
-    morse_map.insert('B', "-...");
-    morse_map.insert('C', "-.-.");
-    morse_map.insert('D', "-..");
-    morse_map.insert('E', ".");
-    morse_map.insert('F', "..-.");
-    morse_map.insert('G', "--.");
-    morse_map.insert('H', "....");
-    morse_map.insert('I', "..");
-    morse_map.insert('J', ".---");
-    morse_map.insert('K', "-.-");
-    morse_map.insert('L', ".-..");
-    morse_map.insert('M', "--");
-    morse_map.insert('N', "-.");
-    morse_map.insert('O', "---");
-    morse_map.insert('P', ".--.");
-    morse_map.insert('Q', "--.-");
-    morse_map.insert('R', ".-.");
-    morse_map.insert('S', "...");
-    morse_map.insert('T', "-");
-    morse_map.insert('U', "..-");
-    morse_map.insert('V', "...-");
-    morse_map.insert('W', ".--");
-    morse_map.insert('X', "-..-");
-    morse_map.insert('Y', "-.--");
-    morse_map.insert('Z', "--..");
-
-    // Numbers
-    morse_map.insert('1', ".----");
-    morse_map.insert('2', "..---");
-    morse_map.insert('3', "...--");
-    morse_map.insert('4', "....-");
-    morse_map.insert('5', ".....");
-    morse_map.insert('6', "-....");
-    morse_map.insert('7', "--...");
-    morse_map.insert('8', "---..");
-    morse_map.insert('9', "----.");
-    morse_map.insert('0', "-----");
-
-    // Punctuation
-    morse_map.insert('.', ".-.-.-");
-    morse_map.insert(',', "--..--");
-    morse_map.insert('?', "..--..");
-    morse_map.insert('\'', ".----.");
-    morse_map.insert('!', "-.-.--");
-    morse_map.insert('/', "-..-.");
-    morse_map.insert('(', "-.--.");
-    morse_map.insert(')', "-.--.-");
-    morse_map.insert('&', ".-...");
-    morse_map.insert(':', "---...");
-    morse_map.insert(';', "-.-.-.");
-    morse_map.insert('=', "-...-");
-    morse_map.insert('+', ".-.-.");
-    morse_map.insert('-', "-....-");
-    morse_map.insert('_', "..--.-");
-    morse_map.insert('"', ".-..-.");
-    morse_map.insert('$', "...-..-");
-    morse_map.insert('@', ".--.-.");
-
-    morse_map
+
+    /// Decodes `group`, failing on an unknown one if [`Unmappable::Error`] is set.
+    fn decode_strict(&mut self, group: Group, plaintext: &mut String) -> Result<(), CipherError> {
+        match self.decode(group, plaintext) {
+            Some(unknown) if self.unmappable == Unmappable::Error => {
+                Err(CipherError::MalformedCiphertext {
+                    position: unknown.position,
+                    reason: format!("{:?} is not a known morse code", unknown.code),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl StreamCipher for Decoder {
+    fn update(&mut self, input: &str, output: &mut String) -> Result<(), CipherError> {
+        for c in input.chars() {
+            if let Some(group) = self.grouper.push(c) {
+                self.decode_strict(group, output)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, output: &mut String) -> Result<(), CipherError> {
+        if let Some(group) = self.grouper.finish() {
+            self.decode_strict(group, output)?;
+        }
+        self.end(output);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Morse;
+
+impl Morse {
+    /// The handling of unmappable text selected in `keys`.
+    pub fn unmappable(keys: &Keys) -> Unmappable {
+        Unmappable::from_name(key_value(keys, "unmappable")).unwrap_or_default()
+    }
+
+    /// The code table selected in `keys`.
+    pub fn table(keys: &Keys) -> Table {
+        Table::from_name(key_value(keys, "table")).unwrap_or_default()
+    }
+}
+
+impl Cipher for Morse {
+    fn id(&self) -> &'static str {
+        "morse"
+    }
+
+    fn name(&self) -> &'static str {
+        "Morse Code"
+    }
+
+    fn description(&self) -> &'static str {
+        "Encodes text as dots and dashes, no key required"
+    }
+
+    fn key_schema(&self) -> Vec<KeyField> {
+        vec![
+            KeyField {
+                id: "table",
+                label: "Code table",
+                flag: "table",
+                kind: KeyKind::Choice {
+                    options: &Table::NAMES,
+                },
+            },
+            KeyField {
+                id: "unmappable",
+                label: "Unmappable characters",
+                flag: "unmappable",
+                kind: KeyKind::Choice {
+                    options: &Unmappable::NAMES,
+                },
+            },
+        ]
+    }
+
+    fn encrypt(&self, plaintext: &str, keys: &Keys) -> Result<String, CipherError> {
+        encode(plaintext, Morse::table(keys), Morse::unmappable(keys))
+    }
+
+    fn decrypt(&self, ciphertext: &str, keys: &Keys) -> Result<String, CipherError> {
+        decode(ciphertext, Morse::table(keys), Morse::unmappable(keys))
+    }
+
+    fn stream(
+        &self,
+        mode: CipherMode,
+        keys: &Keys,
+    ) -> Result<Option<Box<dyn StreamCipher>>, CipherError> {
+        let (table, unmappable) = (Morse::table(keys), Morse::unmappable(keys));
+        Ok(Some(match mode {
+            CipherMode::Encrypt => Box::new(Encoder::new(table, unmappable)),
+            CipherMode::Decrypt => Box::new(Decoder::new(table, unmappable)),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let morse = encrypt_morse_code("Hello, World 42".to_string()).unwrap();
+        assert_eq!(
+            ".... . .-.. .-.. --- --..--  / .-- --- .-. .-.. -..  / ....- ..--- ",
+            morse
+        );
+        assert_eq!("HELLO, WORLD 42 ", decrypt_morse_code(morse).unwrap());
+    }
+
+    #[test]
+    fn test_prosigns() {
+        let morse = encode("<SOS> de <sk> <AR>", Table::Latin, Unmappable::Error).unwrap();
+        assert_eq!("...---...  / -.. .  / ...-.-  / .-.-. ", morse);
+        assert_eq!(
+            "<SOS> DE <SK> + ",
+            decode(&morse, Table::Latin, Unmappable::Error).unwrap()
+        );
+        assert_eq!(
+            Err(CipherError::UnsupportedCharacter {
+                character: '<',
+                position: 3
+            }),
+            encode("ab <XY>", Table::Latin, Unmappable::Error)
+        );
+    }
+
+    #[test]
+    fn test_accented() {
+        let morse = encode("Señor Müller, ça été", Table::Latin, Unmappable::Error).unwrap();
+        assert_eq!(
+            "SEÑOR MÜLLER, ÇA ÉTÉ ",
+            decode(&morse, Table::Latin, Unmappable::Error).unwrap()
+        );
+        // Letters that share a code are read back as the same letter.
+        let morse = encode("Å Ø", Table::Latin, Unmappable::Error).unwrap();
+        assert_eq!(
+            "À Ö ",
+            decode(&morse, Table::Latin, Unmappable::Error).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unmappable() {
+        assert_eq!(
+            Err(CipherError::UnsupportedCharacter {
+                character: '#',
+                position: 2
+            }),
+            encode("a #1", Table::Latin, Unmappable::Error)
+        );
+        assert_eq!(
+            ".-  / .---- ",
+            encode("a # 1", Table::Latin, Unmappable::Skip).unwrap()
+        );
+        assert_eq!(
+            ".- ..--.. .---- ",
+            encode("a#1", Table::Latin, Unmappable::Substitute).unwrap()
+        );
+        // Every table sends the same question mark.
+        for table in [Table::Russian, Table::Wabun] {
+            assert_eq!(
+                "..--.. ",
+                encode("#", table, Unmappable::Substitute).unwrap()
+            );
+        }
+        assert_eq!(
+            "A1 ",
+            decode(".- ........ .----", Table::Latin, Unmappable::Skip).unwrap()
+        );
+        assert_eq!(
+            "A?1 ",
+            decode(".- ........ .----", Table::Latin, Unmappable::Substitute).unwrap()
+        );
+        assert!(decode(".- ........", Table::Latin, Unmappable::Error).is_err());
+        assert_eq!(Some(Unmappable::Skip), Unmappable::from_name(" Skip "));
+    }
+
+    #[test]
+    fn test_tables() {
+        let morse = encode("Привет, мир", Table::Russian, Unmappable::Error).unwrap();
+        assert_eq!(".--. .-. .. .-- . - --..--  / -- .. .-. ", morse);
+        assert_eq!(
+            "ПРИВЕТ, МИР ",
+            decode(&morse, Table::Russian, Unmappable::Error).unwrap()
+        );
+        assert_eq!("PRIWET, MIR ", decrypt_morse_code(morse).unwrap());
+
+        let morse = encode("Ελλάς", Table::Greek, Unmappable::Error).unwrap();
+        assert_eq!(
+            "ΕΛΛΑΣ ",
+            decode(&morse, Table::Greek, Unmappable::Error).unwrap()
+        );
+        let morse = encode("שלום", Table::Hebrew, Unmappable::Error).unwrap();
+        assert_eq!(
+            "שלומ ",
+            decode(&morse, Table::Hebrew, Unmappable::Error).unwrap()
+        );
+        let morse = encode("سلام", Table::Arabic, Unmappable::Error).unwrap();
+        assert_eq!(
+            "سلام ",
+            decode(&morse, Table::Arabic, Unmappable::Error).unwrap()
+        );
+        assert!(encode("Ελλάς", Table::Russian, Unmappable::Error).is_err());
+    }
+
+    #[test]
+    fn test_switching() {
+        // Kana in Latin text are sent in Wabun between DO and SN.
+        let morse = encode("CQ ガイジン QRZ", Table::Latin, Unmappable::Error).unwrap();
+        assert_eq!(
+            "-.-. --.-  / -..--- .-.. .. .- --.-. .. .-.-.  / ...-. --.- .-. --.. ",
+            morse
+        );
+        assert_eq!(
+            "CQ ガイジン QRZ ",
+            decode(&morse, Table::Latin, Unmappable::Error).unwrap()
+        );
+        // Starting in Wabun, the same codes read differently.
+        assert_eq!(
+            "ニネ ",
+            decode("-.-. --.-", Table::Wabun, Unmappable::Error).unwrap()
+        );
+        let morse = encode("ひらがな <SN> ok", Table::Wabun, Unmappable::Error).unwrap();
+        assert_eq!(
+            "ヒラガナ OK ",
+            decode(&morse, Table::Wabun, Unmappable::Error).unwrap()
+        );
+        let morse = encode("Москва Moscow", Table::Russian, Unmappable::Error).unwrap();
+        assert_eq!(
+            "МОСКВА MOSCOW ",
+            decode(&morse, Table::Russian, Unmappable::Error).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tolerant() {
+        let text = "HELLO WORLD ";
+        for morse in [
+            ".... . .-.. .-.. --- | .-- --- .-. .-.. -..",
+            "....  .  .-..  .-..  ---   .--  ---  .-.  .-..  -..",
+            ".... . .-.. .-.. ---\n.-- --- .-. .-.. -..\n",
+            "···· · ·−·· ·−·· −−− / ·−− −−− ·−· ·−·· −··",
+            "•••• • •_•• •_•• ___ // •__ ___ •_• •_•• _••",
+        ] {
+            assert_eq!(
+                text,
+                decrypt_morse_code(morse.to_string()).unwrap(),
+                "{morse}"
+            );
+        }
+    }
+
+    /// Runs `cipher` over `text` one character at a time.
+    fn stream(mut cipher: impl StreamCipher, text: &str) -> Result<String, CipherError> {
+        let mut output = String::new();
+        for c in text.chars() {
+            cipher.update(c.encode_utf8(&mut [0; 4]), &mut output)?;
+        }
+        cipher.finish(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_stream() {
+        for (text, table) in [
+            ("<SOS> de <sk> <AR> <s", Table::Latin),
+            ("CQ ガイジン QRZ", Table::Latin),
+            ("ひらがな <SN> ok", Table::Wabun),
+            ("Москва Moscow", Table::Russian),
+        ] {
+            let morse = encode(text, table, Unmappable::Substitute).unwrap();
+            let encoder = Encoder::new(table, Unmappable::Substitute);
+            assert_eq!(morse, stream(encoder, text).unwrap());
+            let decoder = Decoder::new(table, Unmappable::Error);
+            assert_eq!(
+                decode(&morse, table, Unmappable::Error).unwrap(),
+                stream(decoder, &morse).unwrap()
+            );
+        }
+        let encoder = Encoder::new(Table::Latin, Unmappable::Error);
+        assert_eq!(
+            encode("ab <XY>", Table::Latin, Unmappable::Error),
+            stream(encoder, "ab <XY>")
+        );
+        let decoder = Decoder::new(Table::Latin, Unmappable::Error);
+        let morse = ".- .- / .......... .-";
+        assert_eq!(
+            decode(morse, Table::Latin, Unmappable::Error),
+            stream(decoder, morse)
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let decoded = decode_report(".- ....... -.-x / .-", Table::Latin, Unmappable::Substitute);
+        assert_eq!("A?? A ", decoded.text);
+        assert_eq!(
+            vec![
+                UnknownGroup {
+                    code: ".......".to_string(),
+                    position: 3
+                },
+                UnknownGroup {
+                    code: "-.-x".to_string(),
+                    position: 11
+                }
+            ],
+            decoded.unknown
+        );
+        assert_eq!(
+            Err(CipherError::MalformedCiphertext {
+                position: 8,
+                reason: "\"..........\" is not a known morse code".to_string()
+            }),
+            decode(".- .- / .......... .-", Table::Latin, Unmappable::Error)
+        );
+    }
 }
@@ -0,0 +1,573 @@
+//! The code tables of the scripts Morse code is sent in. The non-Latin tables reuse the Latin
+//! codes for different letters, so which table applies has to be agreed on or signalled: as in
+//! Japanese practice, DO switches from Latin to the other table and SN switches back.
+
+/// Switches from Latin to the other table (DO).
+pub const SHIFT_IN: &str = "-..---";
+/// Switches back to Latin (SN).
+pub const SHIFT_OUT: &str = "...-.";
+
+/// A code table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Table {
+    /// A-Z and accented Latin letters.
+    #[default]
+    Latin,
+    /// The Russian Cyrillic alphabet.
+    Russian,
+    Greek,
+    Hebrew,
+    Arabic,
+    /// Japanese katakana (Wabun code). Hiragana is sent as katakana, and voiced kana as the plain
+    /// kana followed by a (han)dakuten.
+    Wabun,
+}
+
+impl Table {
+    /// The names accepted by [`Table::from_name`], the default first.
+    pub const NAMES: [&'static str; 6] = ["latin", "russian", "greek", "hebrew", "arabic", "wabun"];
+
+    pub fn from_name(name: &str) -> Option<Table> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "latin" => Some(Table::Latin),
+            "russian" | "cyrillic" => Some(Table::Russian),
+            "greek" => Some(Table::Greek),
+            "hebrew" => Some(Table::Hebrew),
+            "arabic" => Some(Table::Arabic),
+            "wabun" | "japanese" | "kana" => Some(Table::Wabun),
+            _ => None,
+        }
+    }
+
+    /// The table [`SHIFT_IN`] switches to when this one is selected: the table itself, or Wabun
+    /// for Latin.
+    pub fn foreign(&self) -> Table {
+        match self {
+            Table::Latin => Table::Wabun,
+            table => *table,
+        }
+    }
+
+    /// The letters of the table with their codes.
+    pub fn letters(&self) -> &'static [(char, &'static str)] {
+        match self {
+            Table::Latin => &LATIN,
+            Table::Russian => &RUSSIAN,
+            Table::Greek => &GREEK,
+            Table::Hebrew => &HEBREW,
+            Table::Arabic => &ARABIC,
+            Table::Wabun => &WABUN,
+        }
+    }
+
+    /// Letters that are sent with the code of another one, and so read back as that one.
+    fn aliases(&self) -> &'static [(char, char)] {
+        match self {
+            Table::Latin => &[
+                ('Å', 'À'),
+                ('Ą', 'Ä'),
+                ('Æ', 'Ä'),
+                ('Ć', 'Ç'),
+                ('Ĉ', 'Ç'),
+                ('Ę', 'É'),
+                ('Ł', 'È'),
+                ('Ń', 'Ñ'),
+                ('Ó', 'Ö'),
+                ('Ø', 'Ö'),
+            ],
+            Table::Russian => &[('Ё', 'Е')],
+            Table::Greek => &[
+                ('Ά', 'Α'),
+                ('Έ', 'Ε'),
+                ('Ή', 'Η'),
+                ('Ί', 'Ι'),
+                ('Ϊ', 'Ι'),
+                ('Ό', 'Ο'),
+                ('Ύ', 'Υ'),
+                ('Ϋ', 'Υ'),
+                ('Ώ', 'Ω'),
+            ],
+            Table::Hebrew => &[('ך', 'כ'), ('ם', 'מ'), ('ן', 'נ'), ('ף', 'פ'), ('ץ', 'צ')],
+            Table::Arabic => &[
+                ('أ', 'ا'),
+                ('إ', 'ا'),
+                ('آ', 'ا'),
+                ('ى', 'ي'),
+                ('ئ', 'ي'),
+                ('ؤ', 'و'),
+                ('ة', 'ه'),
+            ],
+            Table::Wabun => &[
+                ('ァ', 'ア'),
+                ('ィ', 'イ'),
+                ('ゥ', 'ウ'),
+                ('ェ', 'エ'),
+                ('ォ', 'オ'),
+                ('ッ', 'ツ'),
+                ('ャ', 'ヤ'),
+                ('ュ', 'ユ'),
+                ('ョ', 'ヨ'),
+                ('ヮ', 'ワ'),
+            ],
+        }
+    }
+
+    /// The codes that send `c` in this table, which can be two for a voiced kana. Digits and
+    /// punctuation are shared by all tables.
+    pub fn encode(&self, c: char) -> Option<Vec<&'static str>> {
+        let mut upper = c.to_uppercase();
+        let c = match (upper.next(), upper.next()) {
+            (Some(upper), None) => upper,
+            _ => c,
+        };
+        let (c, mark) = match self {
+            Table::Wabun => split_voicing(to_katakana(c)),
+            _ => (c, None),
+        };
+        let c = self
+            .aliases()
+            .iter()
+            .find(|(alias, _)| *alias == c)
+            .map_or(c, |(_, canonical)| *canonical);
+        let code = self.code(c).or_else(|| SHARED_TABLE.code(c))?;
+        Some(match mark {
+            Some(mark) => vec![code, WABUN_TABLE.code(mark)?],
+            None => vec![code],
+        })
+    }
+
+    /// The character sent as `code` in this table. Where a letter of the table and a shared
+    /// punctuation mark have the same code, the letter wins.
+    pub fn decode(&self, code: &str) -> Option<char> {
+        let letter = match self {
+            Table::Latin => LATIN_TABLE.char(code),
+            Table::Russian => RUSSIAN_TABLE.char(code),
+            Table::Greek => GREEK_TABLE.char(code),
+            Table::Hebrew => HEBREW_TABLE.char(code),
+            Table::Arabic => ARABIC_TABLE.char(code),
+            Table::Wabun => WABUN_TABLE.char(code),
+        };
+        letter.or_else(|| SHARED_TABLE.char(code))
+    }
+
+    fn code(&self, c: char) -> Option<&'static str> {
+        match self {
+            Table::Latin => LATIN_TABLE.code(c),
+            Table::Russian => RUSSIAN_TABLE.code(c),
+            Table::Greek => GREEK_TABLE.code(c),
+            Table::Hebrew => HEBREW_TABLE.code(c),
+            Table::Arabic => ARABIC_TABLE.code(c),
+            Table::Wabun => WABUN_TABLE.code(c),
+        }
+    }
+}
+
+/// Longest code in the tables, that of `<SOS>`.
+pub const MAX_CODE: usize = 9;
+/// Nodes in a complete binary trie of codes up to [`MAX_CODE`] long.
+const TRIE_SIZE: usize = 2 << MAX_CODE;
+
+/// The node of `code` in a binary trie stored as an array: the root is node 1, and the children
+/// of node `i` are `2i` for a dot and `2i + 1` for a dash.
+const fn trie_index(code: &[u8]) -> Option<usize> {
+    if code.is_empty() || code.len() > MAX_CODE {
+        return None;
+    }
+    let mut index = 1;
+    let mut i = 0;
+    while i < code.len() {
+        index = match code[i] {
+            b'.' => 2 * index,
+            b'-' => 2 * index + 1,
+            _ => return None,
+        };
+        i += 1;
+    }
+    Some(index)
+}
+
+/// Both directions of a table, built at compile time: a trie from code to character and a list
+/// sorted by character to binary search.
+struct CodeTable<const N: usize> {
+    by_code: [char; TRIE_SIZE],
+    by_char: [(char, &'static str); N],
+}
+
+impl<const N: usize> CodeTable<N> {
+    const fn new(letters: [(char, &'static str); N]) -> Self {
+        let mut by_code = ['\0'; TRIE_SIZE];
+        let mut by_char = letters;
+        let mut i = 0;
+        while i < N {
+            let (c, code) = letters[i];
+            match trie_index(code.as_bytes()) {
+                Some(index) if by_code[index] == '\0' => by_code[index] = c,
+                _ => panic!("invalid or repeated code"),
+            }
+            // Insertion sort
+            let mut j = i;
+            while j > 0 && by_char[j - 1].0 as u32 > by_char[j].0 as u32 {
+                let swap = by_char[j];
+                by_char[j] = by_char[j - 1];
+                by_char[j - 1] = swap;
+                j -= 1;
+            }
+            i += 1;
+        }
+        CodeTable { by_code, by_char }
+    }
+
+    fn code(&self, c: char) -> Option<&'static str> {
+        self.by_char
+            .binary_search_by_key(&c, |(letter, _)| *letter)
+            .ok()
+            .map(|i| self.by_char[i].1)
+    }
+
+    fn char(&self, code: &str) -> Option<char> {
+        trie_index(code.as_bytes())
+            .map(|index| self.by_code[index])
+            .filter(|&c| c != '\0')
+    }
+}
+
+static SHARED_TABLE: CodeTable<28> = CodeTable::new(SHARED);
+static LATIN_TABLE: CodeTable<42> = CodeTable::new(LATIN);
+static RUSSIAN_TABLE: CodeTable<32> = CodeTable::new(RUSSIAN);
+static GREEK_TABLE: CodeTable<24> = CodeTable::new(GREEK);
+static HEBREW_TABLE: CodeTable<22> = CodeTable::new(HEBREW);
+static ARABIC_TABLE: CodeTable<29> = CodeTable::new(ARABIC);
+static WABUN_TABLE: CodeTable<53> = CodeTable::new(WABUN);
+const DAKUTEN: char = '゛';
+const HANDAKUTEN: char = '゜';
+/// Kana that take a dakuten, each directly followed by its voiced form in Unicode.
+const VOICEABLE: &str = "カキクケコサシスセソタチツテトハヒフヘホ";
+/// Kana that take a handakuten, each followed by its voiced and then its half-voiced form.
+const HALF_VOICEABLE: &str = "ハヒフヘホ";
+
+fn to_katakana(c: char) -> char {
+    match c {
+        'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Splits a voiced kana into the plain kana and its mark.
+fn split_voicing(c: char) -> (char, Option<char>) {
+    let shifted = |by: u32| {
+        (c as u32)
+            .checked_sub(by)
+            .and_then(char::from_u32)
+            .unwrap_or(c)
+    };
+    if c == 'ヴ' {
+        ('ウ', Some(DAKUTEN))
+    } else if VOICEABLE.contains(shifted(1)) {
+        (shifted(1), Some(DAKUTEN))
+    } else if HALF_VOICEABLE.contains(shifted(2)) {
+        (shifted(2), Some(HANDAKUTEN))
+    } else {
+        (c, None)
+    }
+}
+
+/// Puts a (han)dakuten received after a kana back onto it, if the kana takes one.
+pub(crate) fn join_voicing(kana: char, mark: char) -> Option<char> {
+    let shifted = |by: u32| char::from_u32(kana as u32 + by);
+    match mark {
+        DAKUTEN if kana == 'ウ' => Some('ヴ'),
+        DAKUTEN if VOICEABLE.contains(kana) => shifted(1),
+        HANDAKUTEN if HALF_VOICEABLE.contains(kana) => shifted(2),
+        _ => None,
+    }
+}
+
+/// Digits and punctuation, the same in every table.
+pub const SHARED: [(char, &str); 28] = [
+    ('1', ".----"),
+    ('2', "..---"),
+    ('3', "...--"),
+    ('4', "....-"),
+    ('5', "....."),
+    ('6', "-...."),
+    ('7', "--..."),
+    ('8', "---.."),
+    ('9', "----."),
+    ('0', "-----"),
+    ('.', ".-.-.-"),
+    (',', "--..--"),
+    ('?', "..--.."),
+    ('\'', ".----."),
+    ('!', "-.-.--"),
+    ('/', "-..-."),
+    ('(', "-.--."),
+    (')', "-.--.-"),
+    ('&', ".-..."),
+    (':', "---..."),
+    (';', "-.-.-."),
+    ('=', "-...-"),
+    ('+', ".-.-."),
+    ('-', "-....-"),
+    ('_', "..--.-"),
+    ('"', ".-..-."),
+    ('$', "...-..-"),
+    ('@', ".--.-."),
+];
+
+pub const LATIN: [(char, &str); 42] = [
+    ('A', ".-"),
+    ('B', "-..."),
+    ('C', "-.-."),
+    ('D', "-.."),
+    ('E', "."),
+    ('F', "..-."),
+    ('G', "--."),
+    ('H', "...."),
+    ('I', ".."),
+    ('J', ".---"),
+    ('K', "-.-"),
+    ('L', ".-.."),
+    ('M', "--"),
+    ('N', "-."),
+    ('O', "---"),
+    ('P', ".--."),
+    ('Q', "--.-"),
+    ('R', ".-."),
+    ('S', "..."),
+    ('T', "-"),
+    ('U', "..-"),
+    ('V', "...-"),
+    ('W', ".--"),
+    ('X', "-..-"),
+    ('Y', "-.--"),
+    ('Z', "--.."),
+    ('À', ".--.-"),
+    ('Ä', ".-.-"),
+    ('Ç', "-.-.."),
+    ('Ð', "..--."),
+    ('É', "..-.."),
+    ('È', ".-..-"),
+    ('Ĝ', "--.-."),
+    ('Ĥ', "----"),
+    ('Ĵ', ".---."),
+    ('Ñ', "--.--"),
+    ('Ö', "---."),
+    ('Ś', "...-..."),
+    ('Þ', ".--.."),
+    ('Ü', "..--"),
+    ('Ź', "--..-."),
+    ('Ż', "--..-"),
+];
+
+pub const RUSSIAN: [(char, &str); 32] = [
+    ('А', ".-"),
+    ('Б', "-..."),
+    ('В', ".--"),
+    ('Г', "--."),
+    ('Д', "-.."),
+    ('Е', "."),
+    ('Ж', "...-"),
+    ('З', "--.."),
+    ('И', ".."),
+    ('Й', ".---"),
+    ('К', "-.-"),
+    ('Л', ".-.."),
+    ('М', "--"),
+    ('Н', "-."),
+    ('О', "---"),
+    ('П', ".--."),
+    ('Р', ".-."),
+    ('С', "..."),
+    ('Т', "-"),
+    ('У', "..-"),
+    ('Ф', "..-."),
+    ('Х', "...."),
+    ('Ц', "-.-."),
+    ('Ч', "---."),
+    ('Ш', "----"),
+    ('Щ', "--.-"),
+    ('Ъ', "--.--"),
+    ('Ы', "-.--"),
+    ('Ь', "-..-"),
+    ('Э', "..-.."),
+    ('Ю', "..--"),
+    ('Я', ".-.-"),
+];
+
+pub const GREEK: [(char, &str); 24] = [
+    ('Α', ".-"),
+    ('Β', "-..."),
+    ('Γ', "--."),
+    ('Δ', "-.."),
+    ('Ε', "."),
+    ('Ζ', "--.."),
+    ('Η', "...."),
+    ('Θ', "-.-."),
+    ('Ι', ".."),
+    ('Κ', "-.-"),
+    ('Λ', ".-.."),
+    ('Μ', "--"),
+    ('Ν', "-."),
+    ('Ξ', "-..-"),
+    ('Ο', "---"),
+    ('Π', ".--."),
+    ('Ρ', ".-."),
+    ('Σ', "..."),
+    ('Τ', "-"),
+    ('Υ', "-.--"),
+    ('Φ', "..-."),
+    ('Χ', "----"),
+    ('Ψ', "--.-"),
+    ('Ω', ".--"),
+];
+
+pub const HEBREW: [(char, &str); 22] = [
+    ('א', ".-"),
+    ('ב', "-..."),
+    ('ג', "--."),
+    ('ד', "-.."),
+    ('ה', "---"),
+    ('ו', "."),
+    ('ז', "--.."),
+    ('ח', "...."),
+    ('ט', "..-"),
+    ('י', ".."),
+    ('כ', "-.-"),
+    ('ל', ".-.."),
+    ('מ', "--"),
+    ('נ', "-."),
+    ('ס', "-.-."),
+    ('ע', ".---"),
+    ('פ', ".--."),
+    ('צ', ".--"),
+    ('ק', "--.-"),
+    ('ר', ".-."),
+    ('ש', "..."),
+    ('ת', "-"),
+];
+
+pub const ARABIC: [(char, &str); 29] = [
+    ('ا', ".-"),
+    ('ب', "-..."),
+    ('ت', "-"),
+    ('ث', "-.-."),
+    ('ج', ".---"),
+    ('ح', "...."),
+    ('خ', "---"),
+    ('د', "-.."),
+    ('ذ', "--.."),
+    ('ر', ".-."),
+    ('ز', "---."),
+    ('س', "..."),
+    ('ش', "----"),
+    ('ص', "-..-"),
+    ('ض', "...-"),
+    ('ط', "..-"),
+    ('ظ', "-.--"),
+    ('ع', ".-.-"),
+    ('غ', "--."),
+    ('ف', "..-."),
+    ('ق', "--.-"),
+    ('ك', "-.-"),
+    ('ل', ".-.."),
+    ('م', "--"),
+    ('ن', "-."),
+    ('ه', "..-.."),
+    ('و', ".--"),
+    ('ي', ".."),
+    ('ء', "."),
+];
+
+pub const WABUN: [(char, &str); 53] = [
+    ('ア', "--.--"),
+    ('イ', ".-"),
+    ('ウ', "..-"),
+    ('エ', "-.---"),
+    ('オ', ".-..."),
+    ('カ', ".-.."),
+    ('キ', "-.-.."),
+    ('ク', "...-"),
+    ('ケ', "-.--"),
+    ('コ', "----"),
+    ('サ', "-.-.-"),
+    ('シ', "--.-."),
+    ('ス', "---.-"),
+    ('セ', ".---."),
+    ('ソ', "---."),
+    ('タ', "-."),
+    ('チ', "..-."),
+    ('ツ', ".--."),
+    ('テ', ".-.--"),
+    ('ト', "..-.."),
+    ('ナ', ".-."),
+    ('ニ', "-.-."),
+    ('ヌ', "...."),
+    ('ネ', "--.-"),
+    ('ノ', "..--"),
+    ('ハ', "-..."),
+    ('ヒ', "--..-"),
+    ('フ', "--.."),
+    ('ヘ', "."),
+    ('ホ', "-.."),
+    ('マ', "-..-"),
+    ('ミ', "..-.-"),
+    ('ム', "-"),
+    ('メ', "-...-"),
+    ('モ', "-..-."),
+    ('ヤ', ".--"),
+    ('ユ', "-..--"),
+    ('ヨ', "--"),
+    ('ラ', "..."),
+    ('リ', "--."),
+    ('ル', "-.--."),
+    ('レ', "---"),
+    ('ロ', ".-.-"),
+    ('ワ', "-.-"),
+    ('ヰ', ".-..-"),
+    ('ヱ', ".--.."),
+    ('ヲ', ".---"),
+    ('ン', ".-.-."),
+    (DAKUTEN, ".."),
+    (HANDAKUTEN, "..--."),
+    ('ー', ".--.-"),
+    ('、', ".-.-.-"),
+    ('。', ".-.-.."),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables() {
+        for name in Table::NAMES {
+            let table = Table::from_name(name).unwrap();
+            let letters = table.letters();
+            for (i, (letter, code)) in letters.iter().enumerate() {
+                assert!(
+                    letters[..i].iter().all(|(_, other)| other != code),
+                    "{letter} in {name}"
+                );
+                assert_ne!(SHIFT_IN, *code);
+                assert_ne!(SHIFT_OUT, *code);
+                assert_eq!(Some(*letter), table.decode(code));
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(Some(vec![".-"]), Table::Russian.encode('а'));
+        assert_eq!(Table::Russian.encode('Е'), Table::Russian.encode('ё'));
+        assert_eq!(Table::Greek.encode('Σ'), Table::Greek.encode('ς'));
+        assert_eq!(Table::Hebrew.encode('מ'), Table::Hebrew.encode('ם'));
+        assert_eq!(Some(vec![".-.."]), Table::Wabun.encode('か'));
+        assert_eq!(Some(vec![".-..", ".."]), Table::Wabun.encode('ガ'));
+        assert_eq!(Some(vec!["-...", "..--."]), Table::Wabun.encode('ぱ'));
+        assert_eq!(Some('ガ'), join_voicing('カ', DAKUTEN));
+        assert_eq!(Some('パ'), join_voicing('ハ', HANDAKUTEN));
+        assert_eq!(None, join_voicing('ア', DAKUTEN));
+        assert_eq!(Some(vec!["....."]), Table::Arabic.encode('5'));
+        assert_eq!(None, Table::Greek.encode('A'));
+    }
+}
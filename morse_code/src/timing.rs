@@ -0,0 +1,311 @@
+//! Morse code as it is keyed: a sequence of tone on and off periods measured in dits. Every way
+//! of sending Morse (audio, a flashing light, a keying line) is derived from this one model, and
+//! recordings are read back into it.
+use std::time::Duration;
+
+use utils::error::CipherError;
+
+use crate::{decrypt_morse_code, encrypt_morse_code, groups};
+
+/// Lengths of the gaps in dits. A dit is always 1 and a dah 3.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spacing {
+    /// Between the elements of a character.
+    pub element: f64,
+    /// Between the characters of a word.
+    pub letter: f64,
+    /// Between words.
+    pub word: f64,
+}
+
+impl Default for Spacing {
+    fn default() -> Self {
+        Spacing {
+            element: 1.0,
+            letter: 3.0,
+            word: 7.0,
+        }
+    }
+}
+
+impl Spacing {
+    /// Farnsworth timing: characters sent at `wpm` with the gaps between characters and words
+    /// stretched so the text as a whole goes at `overall_wpm`. The extra time is split between
+    /// the two kinds of gap in the standard 3 to 7 ratio.
+    pub fn farnsworth(wpm: f64, overall_wpm: f64) -> Self {
+        if overall_wpm >= wpm {
+            return Spacing::default();
+        }
+        // PARIS has 31 dits of characters and 19 dits of gaps (3 * 4 + 7).
+        let delay = (60.0 * wpm - 37.2 * overall_wpm) / (wpm * overall_wpm);
+        let dit = 1.2 / wpm;
+        Spacing {
+            element: 1.0,
+            letter: 3.0 * delay / 19.0 / dit,
+            word: 7.0 * delay / 19.0 / dit,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), CipherError> {
+        if self.element > 0.0 && self.letter > self.element && self.word > self.letter {
+            Ok(())
+        } else {
+            Err(CipherError::InvalidKey(
+                "the gaps must grow from elements to letters to words".to_string(),
+            ))
+        }
+    }
+}
+
+/// A period with the tone on or off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub on: bool,
+    /// Length in dits.
+    pub units: f64,
+}
+
+/// The key going down (tone on) or up at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// Time since the start of the transmission.
+    pub at: Duration,
+    pub down: bool,
+}
+
+/// Keyed Morse code, starting and ending with a tone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timing {
+    spans: Vec<Span>,
+    spacing: Spacing,
+}
+
+impl Timing {
+    /// Builds a timing from spans, merging neighbours that are both on or both off and dropping
+    /// leading and trailing gaps. `spacing` is what the gaps are compared with when the timing is
+    /// turned back into text.
+    pub fn new(spans: impl IntoIterator<Item = Span>, spacing: Spacing) -> Self {
+        let mut merged: Vec<Span> = Vec::new();
+        for span in spans {
+            if span.units <= 0.0 || (merged.is_empty() && !span.on) {
+                continue;
+            }
+            match merged.last_mut() {
+                Some(last) if last.on == span.on => last.units += span.units,
+                _ => merged.push(span),
+            }
+        }
+        if merged.last().is_some_and(|span| !span.on) {
+            merged.pop();
+        }
+        Timing {
+            spans: merged,
+            spacing,
+        }
+    }
+
+    /// Keys Morse code as produced by [`crate::encrypt_morse_code`]: dots and dashes, with
+    /// whitespace between characters and `/` between words. The other ways of writing Morse code
+    /// that [`crate::decode`] reads are accepted too.
+    pub fn from_morse(morse: &str, spacing: Spacing) -> Result<Self, CipherError> {
+        spacing.validate()?;
+        let mut spans = Vec::new();
+        for group in groups(morse) {
+            if !spans.is_empty() {
+                let units = if group.new_word {
+                    spacing.word
+                } else {
+                    spacing.letter
+                };
+                spans.push(Span { on: false, units });
+            }
+            for (offset, symbol) in group.code.chars().enumerate() {
+                let units = match symbol {
+                    '.' => 1.0,
+                    '-' => 3.0,
+                    character => {
+                        return Err(CipherError::UnsupportedCharacter {
+                            character,
+                            position: group.position + offset,
+                        })
+                    }
+                };
+                if offset > 0 {
+                    spans.push(Span {
+                        on: false,
+                        units: spacing.element,
+                    });
+                }
+                spans.push(Span { on: true, units });
+            }
+        }
+        Ok(Timing::new(spans, spacing))
+    }
+
+    pub fn from_text(plaintext: &str, spacing: Spacing) -> Result<Self, CipherError> {
+        Timing::from_morse(&encrypt_morse_code(plaintext.to_string())?, spacing)
+    }
+
+    /// Reads key events back, measuring them in dits of length `dit`.
+    pub fn from_events(
+        events: impl IntoIterator<Item = KeyEvent>,
+        dit: Duration,
+        spacing: Spacing,
+    ) -> Self {
+        let mut spans = Vec::new();
+        let mut last: Option<KeyEvent> = None;
+        for event in events {
+            if let Some(last) = last.filter(|last| last.down != event.down) {
+                spans.push(Span {
+                    on: last.down,
+                    units: event.at.saturating_sub(last.at).as_secs_f64() / dit.as_secs_f64(),
+                });
+            }
+            if last.is_none_or(|last| last.down != event.down) {
+                last = Some(event);
+            }
+        }
+        Timing::new(spans, spacing)
+    }
+
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    pub fn spacing(&self) -> Spacing {
+        self.spacing
+    }
+
+    /// Length of the whole transmission in dits.
+    pub fn units(&self) -> f64 {
+        self.spans.iter().map(|span| span.units).sum()
+    }
+
+    /// The spans with a dit lasting `dit` seconds, as (on, seconds).
+    pub fn seconds(&self, dit: f64) -> impl Iterator<Item = (bool, f64)> + '_ {
+        self.spans
+            .iter()
+            .map(move |span| (span.on, span.units * dit))
+    }
+
+    /// The key going down and up, with a dit lasting `dit`.
+    pub fn events(&self, dit: Duration) -> Vec<KeyEvent> {
+        let mut events = Vec::new();
+        let mut units = 0.0;
+        for span in &self.spans {
+            if span.on {
+                let at = |units: f64| dit.mul_f64(units);
+                events.push(KeyEvent {
+                    at: at(units),
+                    down: true,
+                });
+                events.push(KeyEvent {
+                    at: at(units + span.units),
+                    down: false,
+                });
+            }
+            units += span.units;
+        }
+        events
+    }
+
+    /// Writes the timing as dots and dashes. Tones shorter than 2 dits are dots, and each gap
+    /// counts as the kind of gap in [`Timing::spacing`] it is closest to, so timing that is a
+    /// little off is still read.
+    pub fn to_morse(&self) -> String {
+        let spacing = self.spacing;
+        let mut morse = String::new();
+        for span in &self.spans {
+            match (span.on, span.units) {
+                (true, units) => morse.push(if units < 2.0 { '.' } else { '-' }),
+                (false, units) if units < (spacing.element + spacing.letter) / 2.0 => {}
+                (false, units) if units < (spacing.letter + spacing.word) / 2.0 => morse.push(' '),
+                _ => morse.push_str(" / "),
+            }
+        }
+        morse
+    }
+
+    pub fn to_text(&self) -> Result<String, CipherError> {
+        decrypt_morse_code(self.to_morse())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_morse() {
+        let timing = Timing::from_morse(".- / -", Spacing::default()).unwrap();
+        let units: Vec<(bool, f64)> = timing.spans().iter().map(|s| (s.on, s.units)).collect();
+        assert_eq!(
+            vec![
+                (true, 1.0),
+                (false, 1.0),
+                (true, 3.0),
+                (false, 7.0),
+                (true, 3.0)
+            ],
+            units
+        );
+        assert_eq!(15.0, timing.units());
+        // PARIS is 43 dits, 50 with the gap after it.
+        let paris = Timing::from_text("PARIS", Spacing::default()).unwrap();
+        assert_eq!(43.0, paris.units());
+        assert!(Timing::from_morse(".x", Spacing::default()).is_err());
+    }
+
+    #[test]
+    fn test_farnsworth() {
+        let spacing = Spacing::farnsworth(20.0, 10.0);
+        assert!((spacing.word / spacing.letter - 7.0 / 3.0).abs() < 1e-12);
+        // At 10 wpm PARIS and the gap after it take 6 seconds.
+        let paris = Timing::from_text("PARIS", spacing).unwrap();
+        assert!(((paris.units() + spacing.word) * 1.2 / 20.0 - 6.0).abs() < 1e-9);
+        assert_eq!(Spacing::default(), Spacing::farnsworth(20.0, 25.0));
+        assert_eq!("PARIS", paris.to_text().unwrap().trim());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let spacing = Spacing::farnsworth(25.0, 15.0);
+        let timing = Timing::from_text("sos 73", spacing).unwrap();
+        assert_eq!("... --- ... / --... ...--", timing.to_morse());
+        assert_eq!("SOS 73", timing.to_text().unwrap().trim());
+
+        let dit = Duration::from_millis(48);
+        let events = timing.events(dit);
+        assert_eq!(
+            KeyEvent {
+                at: Duration::ZERO,
+                down: true
+            },
+            events[0]
+        );
+        assert_eq!(dit, events[1].at);
+        let heard = Timing::from_events(events, dit, spacing);
+        assert_eq!(timing.to_morse(), heard.to_morse());
+        assert!((timing.units() - heard.units()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_tolerance() {
+        let spans = [
+            (true, 1.3),
+            (false, 0.7),
+            (true, 2.6),
+            (false, 3.8),
+            (true, 0.9),
+        ]
+        .map(|(on, units)| Span { on, units });
+        let timing = Timing::new(spans, Spacing::default());
+        assert_eq!(".- .", timing.to_morse());
+        assert!(Spacing {
+            letter: 8.0,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,375 @@
+//! Turning a recording of Morse code back into text: the tone is picked out with a Goertzel
+//! filter, keyed on and off against a threshold that follows the signal level, and the lengths of
+//! the tones and gaps are sorted into dits, dahs and spaces.
+use std::f64::consts::PI;
+
+use utils::error::CipherError;
+
+use crate::timing::{Spacing, Span, Timing};
+use crate::{decode_report, Table, UnknownGroup, Unmappable};
+
+/// Length of the blocks the signal level is measured over, in seconds.
+const BLOCK_SECONDS: f64 = 0.005;
+/// How fast the tracked signal and noise levels drift towards the current level, in seconds.
+const TRACKING_SECONDS: f64 = 1.0;
+/// Blocks quieter than this fraction of the loudest block are never counted as tone.
+const SILENCE: f64 = 0.1;
+/// Range searched for the tone when it is not given, in Hz.
+const TONE_RANGE: (f64, f64) = (100.0, 3000.0);
+
+/// What was heard in a recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transcription {
+    /// The Morse code in the format [`crate::encrypt_morse_code`] produces.
+    pub morse: String,
+    /// The Latin text, with a `?` for each code group that could not be read.
+    pub text: String,
+    /// The code groups of [`Transcription::morse`] that could not be read, e.g. because a dit was
+    /// lost in the noise.
+    pub unknown: Vec<UnknownGroup>,
+    /// The keying as it was heard, for passing on to other outputs.
+    pub timing: Timing,
+    /// Estimated speed of the characters in words per minute.
+    pub wpm: f64,
+    /// Pitch of the tone in Hz.
+    pub frequency: f64,
+}
+
+/// Transcribes mono samples, e.g. from [`crate::audio::read_wav`]. The tone is searched for
+/// unless `frequency` is given.
+pub fn transcribe(
+    samples: &[f32],
+    sample_rate: u32,
+    frequency: Option<f64>,
+) -> Result<Transcription, CipherError> {
+    let no_tone = || CipherError::MalformedCiphertext {
+        position: 0,
+        reason: "no Morse code tone could be heard".to_string(),
+    };
+    let rate = sample_rate as f64;
+    let block = ((rate * BLOCK_SECONDS) as usize).max(1);
+    let frequency = match frequency {
+        Some(frequency) => frequency,
+        None => find_tone(samples, rate, block).ok_or_else(no_tone)?,
+    };
+    let levels: Vec<f64> = samples
+        .chunks(block)
+        .map(|chunk| goertzel(chunk, frequency, rate))
+        .collect();
+    let mut runs = key(&levels);
+    let block_seconds = block as f64 / rate;
+    for run in &mut runs {
+        run.1 *= block_seconds;
+    }
+    // Leading and trailing silence carry no information.
+    while runs.last().is_some_and(|run| !run.0) {
+        runs.pop();
+    }
+    if runs.first().is_some_and(|run| !run.0) {
+        runs.remove(0);
+    }
+    if runs.is_empty() {
+        return Err(no_tone());
+    }
+
+    // Clicks and dropouts far shorter than a dit are merged into their surroundings before the
+    // speed is estimated again from the cleaned up runs.
+    let dit = estimate_dit(&runs);
+    let runs = merge(runs, 0.25 * dit);
+    let dit = estimate_dit(&runs);
+
+    let timing = Timing::new(
+        runs.iter().map(|&(on, seconds)| Span {
+            on,
+            units: seconds / dit,
+        }),
+        spacing(&runs, dit),
+    );
+    let morse = timing.to_morse();
+    let decoded = decode_report(&morse, Table::Latin, Unmappable::Substitute);
+    Ok(Transcription {
+        morse,
+        text: decoded.text,
+        unknown: decoded.unknown,
+        timing,
+        wpm: 1.2 / dit,
+        frequency,
+    })
+}
+
+/// Power of `frequency` in `samples`, normalized by their number.
+fn goertzel(samples: &[f32], frequency: f64, rate: f64) -> f64 {
+    let coefficient = 2.0 * (2.0 * PI * frequency / rate).cos();
+    let (mut s1, mut s2) = (0.0, 0.0);
+    for &sample in samples {
+        let s = sample as f64 + coefficient * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+    let power = s1 * s1 + s2 * s2 - coefficient * s1 * s2;
+    power.max(0.0).sqrt() / samples.len() as f64
+}
+
+/// The strongest frequency in the loudest blocks of the recording.
+fn find_tone(samples: &[f32], rate: f64, block: usize) -> Option<f64> {
+    let mut blocks: Vec<(f64, &[f32])> = samples
+        .chunks_exact(block)
+        .map(|chunk| (chunk.iter().map(|&s| (s * s) as f64).sum(), chunk))
+        .filter(|(energy, _)| *energy > 0.0)
+        .collect();
+    blocks.sort_by(|a, b| b.0.total_cmp(&a.0));
+    blocks.truncate(32);
+    let highest = TONE_RANGE.1.min(rate / 2.0 - 1.0);
+    let mut best: Option<(f64, f64)> = None;
+    let mut frequency = TONE_RANGE.0;
+    while frequency <= highest {
+        let power: f64 = blocks
+            .iter()
+            .map(|(_, chunk)| goertzel(chunk, frequency, rate))
+            .sum();
+        if best.is_none_or(|(_, best_power)| power > best_power) {
+            best = Some((frequency, power));
+        }
+        frequency += 5.0;
+    }
+    best.filter(|(_, power)| *power > 0.0)
+        .map(|(frequency, _)| frequency)
+}
+
+/// Decides for every block whether the tone is on, and returns the runs of equal blocks as
+/// (on, number of blocks). The threshold sits between a tracked signal level and a tracked noise
+/// level, so slow fading does not break the keying, and switches with some hysteresis.
+fn key(levels: &[f64]) -> Vec<(bool, f64)> {
+    let loudest = levels.iter().copied().fold(0.0, f64::max);
+    let drift = BLOCK_SECONDS / TRACKING_SECONDS;
+    let start = &levels[..levels
+        .len()
+        .min((TRACKING_SECONDS / BLOCK_SECONDS) as usize)];
+    let mut peak = start.iter().copied().fold(0.0, f64::max);
+    let mut floor = start.iter().copied().fold(f64::INFINITY, f64::min);
+
+    let mut runs: Vec<(bool, f64)> = Vec::new();
+    let mut on = false;
+    for &level in levels {
+        peak = if level > peak {
+            level
+        } else {
+            peak + (level - peak) * drift
+        };
+        floor = if level < floor {
+            level
+        } else {
+            floor + (level - floor) * drift
+        };
+        let threshold = if on { 0.4 } else { 0.6 };
+        on = level > floor + threshold * (peak - floor) && level > SILENCE * loudest;
+        match runs.last_mut() {
+            Some(run) if run.0 == on => run.1 += 1.0,
+            _ => runs.push((on, 1.0)),
+        }
+    }
+    runs
+}
+
+/// Splits `values` into a short and a long group, comparing them on a logarithmic scale.
+/// Returns the centre of each group.
+fn two_means(values: &[f64]) -> (f64, f64) {
+    let logs: Vec<f64> = values.iter().map(|value| value.ln()).collect();
+    let mut low = logs.iter().copied().fold(f64::INFINITY, f64::min);
+    let mut high = logs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    for _ in 0..20 {
+        let boundary = (low + high) / 2.0;
+        let mean = |group: Vec<f64>, fallback: f64| {
+            if group.is_empty() {
+                fallback
+            } else {
+                group.iter().sum::<f64>() / group.len() as f64
+            }
+        };
+        let (short, long): (Vec<f64>, Vec<f64>) = logs.iter().partition(|&&log| log < boundary);
+        low = mean(short, low);
+        high = mean(long, high);
+    }
+    (low.exp(), high.exp())
+}
+
+/// Length of a dit in seconds, from the tones if they contain both dits and dahs and otherwise
+/// from comparing the tones with the shortest gaps.
+///
+/// The threshold and the soft edges of the tone make tones look shorter and gaps longer than they
+/// were keyed, so the estimate is refined from each tone together with the gap inside the
+/// character that follows it, which always add up to 2 or 4 dits.
+fn estimate_dit(runs: &[(bool, f64)]) -> f64 {
+    let tones: Vec<f64> = runs.iter().filter(|run| run.0).map(|run| run.1).collect();
+    let gaps: Vec<f64> = runs.iter().filter(|run| !run.0).map(|run| run.1).collect();
+    let (short, long) = two_means(&tones);
+    let dit = if long / short >= 2.0 {
+        let boundary = (short * long).sqrt();
+        let dits: f64 = tones
+            .iter()
+            .map(|&tone| if tone < boundary { tone } else { tone / 3.0 })
+            .sum();
+        dits / tones.len() as f64
+    } else {
+        // Only one kind of element. Gaps are at least a dit, so tones much longer than the
+        // shortest gaps are dahs.
+        let mean = tones.iter().sum::<f64>() / tones.len() as f64;
+        match gaps.is_empty() {
+            false if mean / two_means(&gaps).0 >= 2.0 => mean / 3.0,
+            _ => mean,
+        }
+    };
+
+    let periods: Vec<f64> = runs
+        .windows(2)
+        .filter(|pair| pair[0].0 && pair[1].1 < 2.0 * dit)
+        .map(|pair| {
+            let dits = if pair[0].1 < 2.0 * dit { 2.0 } else { 4.0 };
+            (pair[0].1 + pair[1].1) / dits
+        })
+        .collect();
+    match periods.is_empty() {
+        true => dit,
+        false => periods.iter().sum::<f64>() / periods.len() as f64,
+    }
+}
+
+/// Drops runs shorter than `shortest` by adding them to the run before.
+fn merge(runs: Vec<(bool, f64)>, shortest: f64) -> Vec<(bool, f64)> {
+    let mut merged: Vec<(bool, f64)> = Vec::new();
+    for (on, seconds) in runs {
+        match merged.last_mut() {
+            Some(last) if last.0 == on || seconds < shortest => last.1 += seconds,
+            _ => merged.push((on, seconds)),
+        }
+    }
+    merged
+}
+
+/// The gaps between letters and between words in dits, told apart by clustering so that stretched
+/// (Farnsworth) spacing is read correctly as long as both kinds occur. Otherwise the standard
+/// spacing is assumed.
+fn spacing(runs: &[(bool, f64)], dit: f64) -> Spacing {
+    let spaces: Vec<f64> = runs
+        .iter()
+        .filter(|run| !run.0 && run.1 >= 2.0 * dit)
+        .map(|run| run.1 / dit)
+        .collect();
+    if spaces.is_empty() {
+        return Spacing::default();
+    }
+    match two_means(&spaces) {
+        (letter, word) if word / letter >= 1.8 => Spacing {
+            element: 1.0,
+            letter,
+            word,
+        },
+        _ => Spacing::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{synthesize, AudioSettings};
+    use crate::encrypt_morse_code;
+
+    fn record(text: &str, settings: &AudioSettings) -> Vec<f32> {
+        let morse = encrypt_morse_code(text.to_string()).unwrap();
+        let mut samples: Vec<f32> = vec![0.0; settings.sample_rate as usize / 4];
+        samples.extend(
+            synthesize(&morse, settings)
+                .unwrap()
+                .iter()
+                .map(|&s| s as f32 / 32_768.0),
+        );
+        samples.extend(vec![0.0; settings.sample_rate as usize / 4]);
+        samples
+    }
+
+    #[test]
+    fn test_transcribe() {
+        let settings = AudioSettings::default();
+        let samples = record("SOS the quick brown fox 73", &settings);
+        let transcription = transcribe(&samples, settings.sample_rate, None).unwrap();
+        assert_eq!("SOS THE QUICK BROWN FOX 73", transcription.text.trim());
+        assert!(
+            (transcription.wpm - 20.0).abs() < 1.0,
+            "{}",
+            transcription.wpm
+        );
+        assert!((transcription.frequency - 600.0).abs() <= 10.0);
+    }
+
+    #[test]
+    fn test_speed_and_spacing() {
+        let settings = AudioSettings {
+            wpm: 35.0,
+            farnsworth_wpm: Some(12.0),
+            frequency: 750.0,
+            sample_rate: 8000,
+            ..Default::default()
+        };
+        let samples = record("paris paris", &settings);
+        let transcription = transcribe(&samples, settings.sample_rate, Some(750.0)).unwrap();
+        assert_eq!("PARIS PARIS", transcription.text.trim());
+        assert!(
+            (transcription.wpm - 35.0).abs() < 3.0,
+            "{}",
+            transcription.wpm
+        );
+
+        // Only dahs, or only dits, leave the tones nothing to be compared with but the gaps.
+        let samples = record("MOM", &AudioSettings::default());
+        assert_eq!(
+            "MOM",
+            transcribe(&samples, 44_100, None).unwrap().text.trim()
+        );
+        let samples = record("SIS", &AudioSettings::default());
+        assert_eq!(
+            "SIS",
+            transcribe(&samples, 44_100, None).unwrap().text.trim()
+        );
+    }
+
+    #[test]
+    fn test_noise_and_fading() {
+        let settings = AudioSettings::default();
+        let mut samples = record("cq cq de test", &settings);
+        let mut state: u32 = 1;
+        let length = samples.len() as f32;
+        for (i, sample) in samples.iter_mut().enumerate() {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let noise = (state >> 8) as f32 / (1 << 24) as f32 - 0.5;
+            // The signal fades to a third of its level over the recording.
+            *sample = *sample * (1.0 - 0.66 * i as f32 / length) + 0.3 * noise;
+        }
+        let transcription = transcribe(&samples, settings.sample_rate, None).unwrap();
+        assert_eq!("CQ CQ DE TEST", transcription.text.trim());
+    }
+
+    #[test]
+    fn test_unknown_groups() {
+        let settings = AudioSettings::default();
+        let samples: Vec<f32> = synthesize("-.-. --.- / ........ / -.-", &settings)
+            .unwrap()
+            .iter()
+            .map(|&s| s as f32 / 32_768.0)
+            .collect();
+        let transcription = transcribe(&samples, settings.sample_rate, None).unwrap();
+        assert_eq!("CQ ? K", transcription.text.trim());
+        assert_eq!(
+            vec![UnknownGroup {
+                code: "........".to_string(),
+                position: 12
+            }],
+            transcription.unknown
+        );
+    }
+
+    #[test]
+    fn test_silence() {
+        assert!(transcribe(&[0.0; 44_100], 44_100, None).is_err());
+        assert!(transcribe(&[], 44_100, Some(600.0)).is_err());
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use crate::error::CipherError;
+use crate::stream::StreamCipher;
+
+/// Values entered by the user for each key field, indexed by [`KeyField::id`].
+pub type Keys = HashMap<String, String>;
+
+/// Upper case letters followed by the digits, the character set most ciphers here work over.
+pub const ALPHANUMERIC: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// Describes a single key parameter that a cipher needs before it can run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyField {
+    /// Identifier used to look the value up in [`Keys`].
+    pub id: &'static str,
+    /// Human readable label shown by the front-ends.
+    pub label: &'static str,
+    /// Long option name used on the command line, e.g. `"key"` for `--key`.
+    pub flag: &'static str,
+    /// What kind of value is expected and how it is validated.
+    pub kind: KeyKind,
+}
+
+/// The shape of a key value. Front-ends use it to pick an input widget and to validate what the
+/// user typed before handing it to the cipher.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyKind {
+    /// Free text made of characters from `charset` (case insensitive, whitespace ignored). An
+    /// empty `charset` accepts anything.
+    Text {
+        charset: &'static str,
+        min_len: usize,
+    },
+    /// The numbers `1..=size` in some order, separated by whitespace or commas. Without a `size`
+    /// any non-empty permutation is accepted, its length being the number of values given. With
+    /// `keyword` set, a word can be given instead, see [`parse_permutation`].
+    Permutation { size: Option<usize>, keyword: bool },
+    /// A whole number in `min..=max`.
+    Integer { min: i64, max: i64 },
+    /// A `rows` by `cols` grid using every character of `charset` at most once, written row by
+    /// row with optional whitespace in between.
+    Matrix {
+        rows: usize,
+        cols: usize,
+        charset: &'static str,
+    },
+    /// One of a fixed set of options, the first being the default.
+    Choice { options: &'static [&'static str] },
+}
+
+impl KeyField {
+    /// Short description of the expected format, shown next to the label.
+    pub fn hint(&self) -> Option<String> {
+        match &self.kind {
+            KeyKind::Text { charset, .. } if *charset == ALPHANUMERIC => {
+                Some("letters and digits".to_string())
+            }
+            KeyKind::Text { .. } => None,
+            KeyKind::Permutation { size, keyword } => {
+                let numbers = match size {
+                    Some(size) => format!("num. 1-{size} separated by spaces or commas"),
+                    None => "num. 1-n separated by spaces or commas".to_string(),
+                };
+                Some(if *keyword {
+                    format!("keyword or {numbers}")
+                } else {
+                    numbers
+                })
+            }
+            KeyKind::Integer { min, max } => Some(format!("{min}-{max}")),
+            KeyKind::Matrix { rows, cols, .. } => Some(format!("{rows}x{cols} grid")),
+            KeyKind::Choice { options } => Some(options.join("/")),
+        }
+    }
+
+    /// The value a front-end should pre-fill the field with.
+    pub fn default_value(&self) -> &'static str {
+        match &self.kind {
+            KeyKind::Choice { options } => options.first().copied().unwrap_or_default(),
+            _ => "",
+        }
+    }
+
+    /// Checks `value` against the rules of [`KeyField::kind`], returning a message suitable for
+    /// showing next to the field when it does not fit.
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        match &self.kind {
+            KeyKind::Text { charset, min_len } => {
+                let mut length = 0;
+                for c in value.chars().filter(|c| !c.is_whitespace()) {
+                    if !charset.is_empty() && !charset.contains(c.to_ascii_uppercase()) {
+                        return Err(format!("{c:?} is not allowed here"));
+                    }
+                    length += 1;
+                }
+                if length < *min_len {
+                    return Err(format!("needs at least {min_len} characters"));
+                }
+                Ok(())
+            }
+            KeyKind::Permutation { size, keyword } => {
+                parse_permutation(value, *size, *keyword).map(|_| ())
+            }
+            KeyKind::Integer { min, max } => {
+                let number: i64 = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("{:?} is not a whole number", value.trim()))?;
+                if number < *min || number > *max {
+                    return Err(format!("must be between {min} and {max}"));
+                }
+                Ok(())
+            }
+            KeyKind::Matrix {
+                rows,
+                cols,
+                charset,
+            } => {
+                let cells: Vec<char> = value
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(|c| c.to_ascii_uppercase())
+                    .collect();
+                for (i, c) in cells.iter().enumerate() {
+                    if !charset.contains(*c) {
+                        return Err(format!("{c:?} is not allowed here"));
+                    }
+                    if cells[..i].contains(c) {
+                        return Err(format!("{c:?} is used more than once"));
+                    }
+                }
+                if cells.len() != rows * cols {
+                    return Err(format!(
+                        "needs {} characters, found {}",
+                        rows * cols,
+                        cells.len()
+                    ));
+                }
+                Ok(())
+            }
+            KeyKind::Choice { options } => {
+                if options
+                    .iter()
+                    .any(|option| option.eq_ignore_ascii_case(value.trim()))
+                {
+                    Ok(())
+                } else {
+                    Err(format!("must be one of {}", options.join(", ")))
+                }
+            }
+        }
+    }
+}
+
+/// The directions a cipher can be run in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherMode {
+    Encrypt,
+    Decrypt,
+}
+
+impl CipherMode {
+    /// Display name used in menus.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CipherMode::Encrypt => "Encryption Mode",
+            CipherMode::Decrypt => "Decryption Mode",
+        }
+    }
+}
+
+/// Common interface implemented by every cipher in the workspace so that the front-ends can
+/// drive them without knowing which one they are talking to.
+pub trait Cipher: Debug {
+    /// Short, lowercase identifier (e.g. `"vigenere"`).
+    fn id(&self) -> &'static str;
+
+    /// Display name used in menus.
+    fn name(&self) -> &'static str;
+
+    /// One line summary of the cipher shown next to its name.
+    fn description(&self) -> &'static str;
+
+    /// The modes the cipher supports, in the order they should be offered.
+    fn modes(&self) -> Vec<CipherMode> {
+        vec![CipherMode::Encrypt, CipherMode::Decrypt]
+    }
+
+    /// The key parameters this cipher expects, in the order they should be asked for.
+    fn key_schema(&self) -> Vec<KeyField>;
+
+    fn encrypt(&self, plaintext: &str, keys: &Keys) -> Result<String, CipherError>;
+
+    fn decrypt(&self, ciphertext: &str, keys: &Keys) -> Result<String, CipherError>;
+
+    /// A table that helps when working the cipher by hand with these keys, such as the tableau or
+    /// the square, for the front-ends to show next to the result. `None` if there is none or the
+    /// keys do not make one.
+    fn key_material(&self, _keys: &Keys) -> Option<String> {
+        None
+    }
+
+    /// A [`StreamCipher`] giving the same output as [`Cipher::encrypt`] or [`Cipher::decrypt`]
+    /// without needing the whole text at once, or `None` if the cipher cannot work that way with
+    /// these keys, e.g. because it transposes the whole message.
+    fn stream(
+        &self,
+        _mode: CipherMode,
+        _keys: &Keys,
+    ) -> Result<Option<Box<dyn StreamCipher>>, CipherError> {
+        Ok(None)
+    }
+}
+
+/// Returns the value entered for `id`, or an empty string if the field was left out.
+pub fn key_value<'a>(keys: &'a Keys, id: &str) -> &'a str {
+    keys.get(id).map(|value| value.as_str()).unwrap_or_default()
+}
+
+/// Splits a list of values separated by whitespace and/or commas.
+pub fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+}
+
+/// Reads a permutation of the numbers `1..=n` separated by whitespace and/or commas, where `n`
+/// is `size` if given and the number of values otherwise.
+///
+/// With `keyword` set, a value that is not a list of numbers is read as a keyword instead
+/// (whitespace ignored, case insensitive): its characters are numbered in alphabetical order,
+/// repeated ones from left to right, so `"PRIVACY"` gives `4 5 3 6 1 2 7`.
+pub fn parse_permutation(
+    value: &str,
+    size: Option<usize>,
+    keyword: bool,
+) -> Result<Vec<usize>, String> {
+    let tokens: Vec<&str> = split_list(value).collect();
+    if keyword && tokens.iter().any(|token| token.parse::<usize>().is_err()) {
+        return keyword_order(value, size);
+    }
+    if tokens.is_empty() {
+        return Err("needs at least 1 number".to_string());
+    }
+    let size = size.unwrap_or(tokens.len());
+    let mut seen = vec![false; size];
+    let mut numbers = Vec::with_capacity(size);
+    for token in tokens {
+        let number: usize = token
+            .parse()
+            .map_err(|_| format!("{token:?} is not a number"))?;
+        if number == 0 || number > size {
+            return Err(format!("{number} is not between 1 and {size}"));
+        }
+        if seen[number - 1] {
+            return Err(format!("{number} is used more than once"));
+        }
+        seen[number - 1] = true;
+        numbers.push(number);
+    }
+    if numbers.len() != size {
+        return Err(format!("needs {size} numbers, found {}", numbers.len()));
+    }
+    Ok(numbers)
+}
+
+/// The permutation spelled by the alphabetical order of the characters of `keyword`.
+fn keyword_order(keyword: &str, size: Option<usize>) -> Result<Vec<usize>, String> {
+    let chars: Vec<char> = keyword
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if let Some(c) = chars.iter().find(|c| !ALPHANUMERIC.contains(**c)) {
+        return Err(format!("{c:?} is not allowed here"));
+    }
+    if let Some(size) = size.filter(|&size| size != chars.len()) {
+        return Err(format!("needs {size} characters, found {}", chars.len()));
+    }
+    let mut by_letter: Vec<usize> = (0..chars.len()).collect();
+    by_letter.sort_by_key(|&i| chars[i]);
+    let mut order = vec![0; chars.len()];
+    for (rank, i) in by_letter.into_iter().enumerate() {
+        order[i] = rank + 1;
+    }
+    Ok(order)
+}
+
+/// Validates every field of `schema` against `keys`, returning the first problem found. Fields
+/// missing from `keys` are checked with their [`KeyField::default_value`].
+pub fn validate_keys(schema: &[KeyField], keys: &Keys) -> Result<(), CipherError> {
+    for field in schema {
+        let value = keys
+            .get(field.id)
+            .map(|value| value.as_str())
+            .unwrap_or(field.default_value());
+        field
+            .validate(value)
+            .map_err(|reason| CipherError::InvalidKey(format!("{}: {reason}", field.label)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(kind: KeyKind) -> KeyField {
+        KeyField {
+            id: "key",
+            label: "Key",
+            flag: "key",
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_validate_text() {
+        let keyword = field(KeyKind::Text {
+            charset: ALPHANUMERIC,
+            min_len: 1,
+        });
+        assert_eq!(Ok(()), keyword.validate("lemon 42"));
+        assert!(keyword.validate("lem-on").is_err());
+        assert!(keyword.validate("  ").is_err());
+    }
+
+    #[test]
+    fn test_validate_permutation() {
+        let column_key = field(KeyKind::Permutation {
+            size: Some(3),
+            keyword: false,
+        });
+        assert_eq!(Ok(()), column_key.validate("3 1 2"));
+        assert_eq!(Ok(()), column_key.validate("3,1, 2"));
+        assert!(column_key.validate("3 1 1").is_err());
+        assert!(column_key.validate("3 1").is_err());
+        assert!(column_key.validate("4 1 2").is_err());
+
+        let column_key = field(KeyKind::Permutation {
+            size: None,
+            keyword: false,
+        });
+        assert_eq!(Ok(()), column_key.validate("2 1"));
+        assert_eq!(Ok(()), column_key.validate("4 1 3 2"));
+        assert!(column_key.validate("4 1 2").is_err());
+        assert!(column_key.validate("").is_err());
+        assert!(column_key.validate("privacy").is_err());
+
+        let column_key = field(KeyKind::Permutation {
+            size: None,
+            keyword: true,
+        });
+        assert_eq!(Ok(()), column_key.validate("privacy"));
+        assert_eq!(Ok(()), column_key.validate("3 1 2"));
+        assert!(column_key.validate("3 1 1").is_err());
+        assert!(column_key.validate("pri-vacy").is_err());
+    }
+
+    #[test]
+    fn test_parse_permutation() {
+        assert_eq!(
+            Ok(vec![4, 5, 3, 6, 1, 2, 7]),
+            parse_permutation("PRIVACY", None, true)
+        );
+        // Repeated letters are numbered from left to right.
+        assert_eq!(
+            Ok(vec![3, 1, 4, 2, 5]),
+            parse_permutation("b a l a l", None, true)
+        );
+        assert_eq!(
+            Ok(vec![5, 1, 3, 4, 2, 6]),
+            parse_permutation("5,1,3,4,2,6", None, true)
+        );
+        assert!(parse_permutation("PRIVACY", Some(6), true).is_err());
+        assert!(parse_permutation("5 1 3 4 2 2", None, true).is_err());
+        assert!(parse_permutation("5 1 3 4 2 7", None, true).is_err());
+        assert!(parse_permutation("5 1 x", None, false).is_err());
+    }
+
+    #[test]
+    fn test_validate_matrix() {
+        let square = field(KeyKind::Matrix {
+            rows: 2,
+            cols: 2,
+            charset: "ABCD",
+        });
+        assert_eq!(Ok(()), square.validate("ab\ncd"));
+        assert!(square.validate("aabc").is_err());
+        assert!(square.validate("abc").is_err());
+    }
+}
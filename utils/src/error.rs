@@ -0,0 +1,38 @@
+use std::{error::Error, fmt};
+
+/// Everything that can go wrong while encrypting or decrypting. Positions are zero based character
+/// offsets into the text that was handed to the failing function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CipherError {
+    /// The text contains a character the cipher has no encoding for.
+    UnsupportedCharacter { character: char, position: usize },
+    /// A key is empty, contains characters the cipher cannot use or is otherwise unusable.
+    InvalidKey(String),
+    /// The ciphertext could not have been produced by the cipher.
+    MalformedCiphertext { position: usize, reason: String },
+    /// Two inputs that have to line up do not, e.g. a plaintext and its expanded keyword.
+    LengthMismatch { expected: usize, found: usize },
+}
+
+impl fmt::Display for CipherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CipherError::UnsupportedCharacter {
+                character,
+                position,
+            } => write!(
+                f,
+                "unsupported character {character:?} at position {position}"
+            ),
+            CipherError::InvalidKey(reason) => write!(f, "invalid key: {reason}"),
+            CipherError::MalformedCiphertext { position, reason } => {
+                write!(f, "malformed ciphertext at position {position}: {reason}")
+            }
+            CipherError::LengthMismatch { expected, found } => {
+                write!(f, "length mismatch: expected {expected}, found {found}")
+            }
+        }
+    }
+}
+
+impl Error for CipherError {}
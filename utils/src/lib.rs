@@ -1,3 +1,12 @@
+pub mod cipher;
+pub mod error;
+pub mod ngrams;
+pub mod postprocess;
+pub mod segmentation;
+pub mod stream;
+
+use error::CipherError;
+
 pub fn remove_whitespace(s: &mut String) -> String {
     s.retain(|c| !c.is_whitespace());
     let x = s;
@@ -6,6 +15,9 @@ pub fn remove_whitespace(s: &mut String) -> String {
 
 use regex::Regex;
 
+/// The characters [`remove_punctuation`] takes out, for ciphers that skip them one at a time.
+pub const PUNCTUATION: &str = "<>«»,'.;:";
+
 pub fn remove_punctuation(text: &str) -> String {
     let re = Regex::new(r"<<|>>|<|>|«|»|,|'|\.|;|:").unwrap();
     // Replace all matches with an empty string
@@ -16,18 +28,8 @@ pub fn remove_punctuation(text: &str) -> String {
         .to_string()
 }
 
-pub fn generate_keyword_string(keyword: &mut String, plaintext_len: usize) -> String {
-    if plaintext_len > keyword.len() {
-        let remainder = plaintext_len % keyword.len();
-        let quotient = plaintext_len / keyword.len();
-        let mut keyword_string = keyword.repeat(quotient).to_string();
-        if remainder > 0 {
-            keyword_string.push_str(&keyword[0..remainder]);
-        }
-        keyword_string
-    } else {
-        keyword[0..plaintext_len].to_string()
-    }
+pub fn generate_keyword_string(keyword: &str, plaintext_len: usize) -> String {
+    keyword.chars().cycle().take(plaintext_len).collect()
 }
 
 pub fn remove_repeating_letters(text: String) -> String {
@@ -54,7 +56,7 @@ pub fn remove_charset(charset: Vec<char>, text: String) -> String {
     result
 }
 
-pub fn find_char(grid: &Vec<Vec<char>>, target: char) -> Option<(usize, usize)> {
+pub fn find_char(grid: &[Vec<char>], target: char) -> Option<(usize, usize)> {
     for (row_idx, row) in grid.iter().enumerate() {
         for (col_idx, c) in row.iter().enumerate() {
             if *c == target {
@@ -65,14 +67,22 @@ pub fn find_char(grid: &Vec<Vec<char>>, target: char) -> Option<(usize, usize)>
 
     None
 }
-pub fn transpose<T>(v: Vec<Vec<T>>) -> Vec<Vec<T>>
+pub fn transpose<T>(v: Vec<Vec<T>>) -> Result<Vec<Vec<T>>, CipherError>
 where
     T: Clone,
 {
-    assert!(!v.is_empty());
-    (0..v[0].len())
+    let Some(width) = v.first().map(|row| row.len()) else {
+        return Ok(Vec::new());
+    };
+    if let Some(row) = v.iter().find(|row| row.len() != width) {
+        return Err(CipherError::LengthMismatch {
+            expected: width,
+            found: row.len(),
+        });
+    }
+    Ok((0..width)
         .map(|i| v.iter().map(|inner| inner[i].clone()).collect::<Vec<T>>())
-        .collect()
+        .collect())
 }
 
 #[cfg(test)]
@@ -94,6 +104,18 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_transpose_ragged() {
+        let grid = Vec::from([Vec::from(['a', 'b']), Vec::from(['c'])]);
+        assert_eq!(
+            Err(CipherError::LengthMismatch {
+                expected: 2,
+                found: 1
+            }),
+            transpose(grid)
+        )
+    }
+
     #[test]
     fn test_finder() {
         let grid = Vec::from([Vec::from(['a', 'b']), Vec::from(['c', 'd'])]);
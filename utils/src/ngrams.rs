@@ -0,0 +1,131 @@
+//! Letter trigram statistics of English, for telling candidate decryptions apart when the words
+//! in them are not separated.
+use std::sync::OnceLock;
+
+use crate::segmentation::WORDS;
+
+/// Number of distinct trigrams over A-Z.
+const TRIGRAMS: usize = 26 * 26 * 26;
+
+/// Log10 probabilities of the trigrams of English text written without spaces.
+///
+/// The probabilities are estimated from the word list the segmenter uses, weighting each word by
+/// its frequency. Trigrams that span two words are counted from the letters words tend to end
+/// and start with.
+#[derive(Debug, Clone)]
+pub struct Trigrams {
+    log_probs: Vec<f64>,
+    /// Score of a trigram that never occurs, or contains something other than a letter.
+    floor: f64,
+}
+
+impl Default for Trigrams {
+    fn default() -> Self {
+        let words: Vec<Vec<usize>> = WORDS.lines().map(letters).collect();
+        let weights: Vec<f64> = (0..words.len())
+            .map(|rank| 1.0 / (rank + 1) as f64)
+            .collect();
+
+        let mut counts = vec![0.0; TRIGRAMS];
+        let (mut start1, mut end1) = (vec![0.0; 26], vec![0.0; 26]);
+        let (mut start2, mut end2) = (vec![0.0; 26 * 26], vec![0.0; 26 * 26]);
+        for (word, weight) in words.iter().zip(&weights) {
+            let Some((&first, &last)) = word.first().zip(word.last()) else {
+                continue;
+            };
+            for window in word.windows(3) {
+                counts[index(window[0], window[1], window[2])] += weight;
+            }
+            start1[first] += weight;
+            end1[last] += weight;
+            if word.len() >= 2 {
+                start2[first * 26 + word[1]] += weight;
+                end2[word[word.len() - 2] * 26 + last] += weight;
+            }
+        }
+        let total_weight: f64 = weights.iter().sum();
+        for a in 0..26 {
+            for b in 0..26 {
+                for c in 0..26 {
+                    counts[index(a, b, c)] += (end2[a * 26 + b] * start1[c]
+                        + end1[a] * start2[b * 26 + c])
+                        / total_weight;
+                }
+            }
+        }
+
+        let total: f64 = counts.iter().sum();
+        let rarest = counts
+            .iter()
+            .copied()
+            .filter(|&count| count > 0.0)
+            .fold(f64::INFINITY, f64::min);
+        let floor = (rarest / total).log10() - 1.0;
+        let log_probs = counts
+            .iter()
+            .map(|&count| {
+                if count > 0.0 {
+                    (count / total).log10()
+                } else {
+                    floor
+                }
+            })
+            .collect();
+        Trigrams { log_probs, floor }
+    }
+}
+
+impl Trigrams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sum of the log10 probabilities of every trigram in `text`. The higher, the more
+    /// English-like the text is.
+    pub fn score(&self, text: &[char]) -> f64 {
+        text.windows(3)
+            .map(
+                |window| match (letter(window[0]), letter(window[1]), letter(window[2])) {
+                    (Some(a), Some(b), Some(c)) => self.log_probs[index(a, b, c)],
+                    _ => self.floor,
+                },
+            )
+            .sum()
+    }
+}
+
+/// The shared [`Trigrams`] model, built on first use.
+pub fn english_trigrams() -> &'static Trigrams {
+    static TRIGRAMS: OnceLock<Trigrams> = OnceLock::new();
+    TRIGRAMS.get_or_init(Trigrams::new)
+}
+
+fn letter(c: char) -> Option<usize> {
+    let c = c.to_ascii_uppercase();
+    c.is_ascii_uppercase().then(|| (c as u8 - b'A') as usize)
+}
+
+fn letters(word: &str) -> Vec<usize> {
+    word.chars().filter_map(letter).collect()
+}
+
+fn index(a: usize, b: usize, c: usize) -> usize {
+    (a * 26 + b) * 26 + c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(text: &str) -> f64 {
+        english_trigrams().score(&text.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_score() {
+        assert!(score("THEREISNOPLACELIKEHOME") > score("XQZVKJWPQZXMVBNQJZKWPX"));
+        assert!(score("attackatdawn") > score("ATTACKATDAWM"));
+        assert!(score("ATTACK") > score("ATT4CK"));
+        assert_eq!(0.0, score("AT"));
+    }
+}
@@ -0,0 +1,221 @@
+//! Tidying up decrypted text, which comes out as one upper case run of letters and digits.
+//!
+//! The HTTP provider needs the `http` feature.
+#[cfg(feature = "http")]
+use serde_json::{json, Value};
+use std::{error::Error, fmt};
+
+use crate::segmentation::Segmenter;
+
+/// Why a [`PostProcessor`] could not process the text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostProcessError {
+    /// The provider could not be reached or answered with an error status.
+    Request(String),
+    /// The provider answered, but not with something that contains the processed text.
+    Response(String),
+}
+
+impl fmt::Display for PostProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostProcessError::Request(reason) => write!(f, "request failed: {reason}"),
+            PostProcessError::Response(reason) => write!(f, "unexpected response: {reason}"),
+        }
+    }
+}
+
+impl Error for PostProcessError {}
+
+/// Turns the output of a decryption into something easier to read.
+pub trait PostProcessor: fmt::Debug {
+    fn process(&self, plaintext: &str) -> Result<String, PostProcessError>;
+}
+
+/// Re-inserts the spaces removed before encryption with [`crate::segmentation`]. Runs offline
+/// and never fails.
+#[derive(Debug, Clone, Default)]
+pub struct WordSegmenter {
+    segmenter: Segmenter,
+}
+
+impl WordSegmenter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PostProcessor for WordSegmenter {
+    fn process(&self, plaintext: &str) -> Result<String, PostProcessError> {
+        Ok(self.segmenter.segment(plaintext).join(" "))
+    }
+}
+
+/// Hands the text to an HTTP service, e.g. a language model behind a small proxy.
+///
+/// The text is POSTed to the endpoint as `{"text": "..."}` and the service is expected to answer
+/// with a JSON object of the same shape.
+#[cfg(feature = "http")]
+#[derive(Debug, Clone)]
+pub struct HttpPostProcessor {
+    endpoint: String,
+    api_key: Option<String>,
+}
+
+#[cfg(feature = "http")]
+impl HttpPostProcessor {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        HttpPostProcessor {
+            endpoint: endpoint.into(),
+            api_key: None,
+        }
+    }
+
+    /// Sends `api_key` as a bearer token with every request.
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+}
+
+#[cfg(feature = "http")]
+impl PostProcessor for HttpPostProcessor {
+    fn process(&self, plaintext: &str) -> Result<String, PostProcessError> {
+        let client = reqwest::blocking::Client::new();
+        let mut request = client
+            .post(&self.endpoint)
+            .json(&json!({ "text": plaintext }));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| PostProcessError::Request(e.to_string()))?;
+        let body: Value = response
+            .json()
+            .map_err(|e| PostProcessError::Response(e.to_string()))?;
+        body["text"]
+            .as_str()
+            .map(|text| text.to_string())
+            .ok_or_else(|| PostProcessError::Response("no \"text\" field in the body".to_string()))
+    }
+}
+
+/// The post-processor to use: the HTTP provider at `endpoint` if one is configured, sending
+/// `api_key` if there is one, the offline [`WordSegmenter`] otherwise.
+#[cfg(feature = "http")]
+pub fn post_processor(endpoint: Option<&str>, api_key: Option<&str>) -> Box<dyn PostProcessor> {
+    match (endpoint, api_key) {
+        (Some(endpoint), Some(api_key)) => {
+            Box::new(HttpPostProcessor::new(endpoint).with_api_key(api_key))
+        }
+        (Some(endpoint), None) => Box::new(HttpPostProcessor::new(endpoint)),
+        (None, _) => Box::new(WordSegmenter::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "http")]
+    use std::io::{BufRead, BufReader, Read, Write};
+    #[cfg(feature = "http")]
+    use std::net::TcpListener;
+    #[cfg(feature = "http")]
+    use std::thread;
+
+    /// Answers a single request with `status` and `body`, returning the endpoint URL and a handle
+    /// resolving to the header lines and the body of the request it received.
+    #[cfg(feature = "http")]
+    fn stand_in_server(
+        status: &'static str,
+        body: &'static str,
+    ) -> (String, thread::JoinHandle<(Vec<String>, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/format", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                headers.push(line.trim().to_string());
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut request = vec![0; content_length];
+            reader.read_exact(&mut request).unwrap();
+            let mut stream = stream;
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            (headers, String::from_utf8(request).unwrap())
+        });
+        (endpoint, handle)
+    }
+
+    #[test]
+    fn test_segment() {
+        let segmenter = WordSegmenter::new();
+        assert_eq!(
+            "IT WAS THE BEST OF TIMES IT WAS THE WORST OF TIMES",
+            segmenter
+                .process("ITWASTHEBESTOFTIMESITWASTHEWORSTOFTIMES")
+                .unwrap()
+        );
+        assert_eq!(
+            "MEET ME AT THE BRIDGE AT 0600",
+            segmenter.process("MEETM EATTH EBRID GEAT0 600").unwrap()
+        );
+        assert_eq!("", segmenter.process("").unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_http() {
+        let (endpoint, request) = stand_in_server("200 OK", r#"{"text": "Attack at dawn."}"#);
+        let processed = HttpPostProcessor::new(endpoint).process("ATTACKATDAWN");
+        assert_eq!(Ok("Attack at dawn.".to_string()), processed);
+        let (headers, body) = request.join().unwrap();
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("ATTACKATDAWN", body["text"]);
+        assert!(!headers
+            .iter()
+            .any(|header| header.to_ascii_lowercase().starts_with("authorization:")));
+
+        let (endpoint, request) = stand_in_server("200 OK", r#"{"text": "Attack at dawn."}"#);
+        let processed = post_processor(Some(&endpoint), Some("s3cret")).process("ATTACKATDAWN");
+        assert_eq!(Ok("Attack at dawn.".to_string()), processed);
+        let (headers, _) = request.join().unwrap();
+        assert!(headers
+            .iter()
+            .any(|header| header.eq_ignore_ascii_case("authorization: Bearer s3cret")));
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_http_errors() {
+        let (endpoint, _) = stand_in_server("500 Internal Server Error", "{}");
+        assert!(matches!(
+            HttpPostProcessor::new(endpoint).process("ATTACKATDAWN"),
+            Err(PostProcessError::Request(_))
+        ));
+        let (endpoint, _) = stand_in_server("200 OK", r#"{"answer": "Attack at dawn."}"#);
+        assert!(matches!(
+            HttpPostProcessor::new(endpoint).process("ATTACKATDAWN"),
+            Err(PostProcessError::Response(_))
+        ));
+    }
+}
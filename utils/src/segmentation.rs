@@ -0,0 +1,176 @@
+//! Putting the spaces back into decrypted text, which the ciphers return as one run of letters
+//! and digits.
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// Common English words, most frequent first.
+pub(crate) const WORDS: &str = include_str!("words.txt");
+
+/// Common pairs of consecutive English words, one pair per line.
+const BIGRAMS: &str = include_str!("bigrams.txt");
+
+/// Longest word the segmenter looks for.
+const MAX_WORD_LENGTH: usize = 20;
+
+/// How much more likely (as a power of ten) a word is right after a word it often follows.
+const BIGRAM_LIFT: f64 = 1.0;
+
+/// An English language model over words and word pairs, used to find the most likely way to
+/// split a run of letters into words.
+#[derive(Debug, Clone)]
+pub struct Segmenter {
+    /// Id of each known word.
+    ids: HashMap<String, usize>,
+    /// Log10 probability of each known word, indexed by id and estimated from its rank with
+    /// Zipf's law.
+    unigrams: Vec<f64>,
+    bigrams: HashSet<(usize, usize)>,
+}
+
+impl Default for Segmenter {
+    fn default() -> Self {
+        let words: Vec<String> = WORDS.lines().map(|word| word.to_uppercase()).collect();
+        let ids: HashMap<String, usize> = words
+            .iter()
+            .enumerate()
+            .map(|(id, word)| (word.clone(), id))
+            .collect();
+        let unigrams = (0..words.len())
+            .map(|rank| (0.1 / (rank + 1) as f64).log10())
+            .collect();
+        let bigrams = BIGRAMS
+            .lines()
+            .filter_map(|line| {
+                let (first, second) = line.split_once(' ')?;
+                Some((
+                    *ids.get(&first.to_uppercase())?,
+                    *ids.get(&second.to_uppercase())?,
+                ))
+            })
+            .collect();
+        Segmenter {
+            ids,
+            unigrams,
+            bigrams,
+        }
+    }
+}
+
+impl Segmenter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Log10 probability of a word on its own. Numbers count as one fairly common word, unknown
+    /// words get less likely the longer they are.
+    fn unigram_score(&self, word: &[char], id: Option<usize>) -> f64 {
+        match id {
+            Some(id) => self.unigrams[id],
+            None if word.iter().all(|c| c.is_ascii_digit()) => -4.0,
+            None => -4.0 - 1.5 * word.len() as f64,
+        }
+    }
+
+    /// Splits `text` into its most likely sequence of words, ignoring the whitespace already in
+    /// it. The words keep the case they had in `text`.
+    ///
+    /// This is a Viterbi search where the state is the position of the last word: `best[end][k]`
+    /// is the score of the best split of the first `end` characters whose last word is `k + 1`
+    /// characters long.
+    pub fn segment(&self, text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let upper: Vec<char> = chars.iter().map(|c| c.to_ascii_uppercase()).collect();
+        let n = chars.len();
+        let word_id = |start: usize, end: usize| -> Option<usize> {
+            self.ids
+                .get(&upper[start..end].iter().collect::<String>())
+                .copied()
+        };
+
+        // (score, length of the previous word, id of the last word)
+        let mut best: Vec<Vec<(f64, usize, Option<usize>)>> = vec![Vec::new(); n + 1];
+        for end in 1..=n {
+            for length in 1..=MAX_WORD_LENGTH.min(end) {
+                let start = end - length;
+                let id = word_id(start, end);
+                let unigram = self.unigram_score(&upper[start..end], id);
+                let entry = if start == 0 {
+                    (unigram, 0, id)
+                } else {
+                    best[start]
+                        .iter()
+                        .enumerate()
+                        .map(|(k, &(score, _, previous))| {
+                            let lift = match (previous, id) {
+                                (Some(previous), Some(id))
+                                    if self.bigrams.contains(&(previous, id)) =>
+                                {
+                                    BIGRAM_LIFT
+                                }
+                                _ => 0.0,
+                            };
+                            (score + (unigram + lift).min(0.0), k + 1, id)
+                        })
+                        .max_by(|a, b| a.0.total_cmp(&b.0))
+                        .unwrap_or((f64::NEG_INFINITY, 0, id))
+                };
+                best[end].push(entry);
+            }
+        }
+
+        let mut words = Vec::new();
+        let mut end = n;
+        let mut length = best[n]
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+            .map(|(k, _)| k + 1)
+            .unwrap_or_default();
+        while end > 0 {
+            let start = end - length;
+            words.push(chars[start..end].iter().collect());
+            length = best[end][end - start - 1].1;
+            end = start;
+        }
+        words.reverse();
+        words
+    }
+}
+
+/// Re-inserts word boundaries into `text` using the embedded English model.
+pub fn restore_spacing(text: &str) -> String {
+    static SEGMENTER: OnceLock<Segmenter> = OnceLock::new();
+    SEGMENTER
+        .get_or_init(Segmenter::new)
+        .segment(text)
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_spacing() {
+        assert_eq!(
+            "IT WAS THE BEST OF TIMES IT WAS THE WORST OF TIMES",
+            restore_spacing("ITWASTHEBESTOFTIMESITWASTHEWORSTOFTIMES")
+        );
+        assert_eq!(
+            "MEET ME AT THE BRIDGE AT 0600",
+            restore_spacing("MEETM EATTH EBRID GEAT0 600")
+        );
+        assert_eq!("attack at dawn", restore_spacing("attackatdawn"));
+        assert_eq!("", restore_spacing(""));
+    }
+
+    #[test]
+    fn test_bigrams() {
+        let segmenter = Segmenter::new();
+        assert!(!segmenter.bigrams.is_empty());
+        assert_eq!(
+            vec!["ONE", "OF", "THE", "BEST"],
+            segmenter.segment("ONEOFTHEBEST")
+        );
+    }
+}
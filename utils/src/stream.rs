@@ -0,0 +1,212 @@
+//! Running ciphers over text that does not have to fit in memory, a piece at a time.
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::error::CipherError;
+
+/// Bytes read from the input at a time by [`transform`].
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A cipher that takes its input in pieces, keeping whatever it needs from one piece to the
+/// next. Feeding the whole text to [`StreamCipher::update`] at once and then calling
+/// [`StreamCipher::finish`] gives the same output as the one-shot function of the cipher.
+pub trait StreamCipher {
+    /// Transforms the next piece of text and appends the result to `output`. Output may be held
+    /// back until more input or [`StreamCipher::finish`] shows what it has to be. Positions in
+    /// errors count from the start of the whole text.
+    fn update(&mut self, input: &str, output: &mut String) -> Result<(), CipherError>;
+
+    /// Appends whatever was held back once the input is exhausted.
+    fn finish(&mut self, output: &mut String) -> Result<(), CipherError>;
+}
+
+impl<C: StreamCipher + ?Sized> StreamCipher for Box<C> {
+    fn update(&mut self, input: &str, output: &mut String) -> Result<(), CipherError> {
+        (**self).update(input, output)
+    }
+
+    fn finish(&mut self, output: &mut String) -> Result<(), CipherError> {
+        (**self).finish(output)
+    }
+}
+
+/// Why streaming stopped.
+#[derive(Debug)]
+pub enum StreamError {
+    /// Reading the input or writing the output failed, or the input is not UTF-8.
+    Io(io::Error),
+    Cipher(CipherError),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Io(error) => write!(f, "{error}"),
+            StreamError::Cipher(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for StreamError {}
+
+impl From<io::Error> for StreamError {
+    fn from(error: io::Error) -> Self {
+        StreamError::Io(error)
+    }
+}
+
+impl From<CipherError> for StreamError {
+    fn from(error: CipherError) -> Self {
+        StreamError::Cipher(error)
+    }
+}
+
+/// Runs `cipher` over everything in `reader`, writing the output to `writer` as it is produced.
+/// Only a chunk of input and its output are held in memory at a time.
+pub fn transform(
+    mut reader: impl Read,
+    mut writer: impl Write,
+    cipher: &mut dyn StreamCipher,
+) -> Result<(), StreamError> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+    // Bytes of a character split between two reads.
+    let mut partial = Vec::new();
+    let mut output = String::new();
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error.into()),
+        };
+        partial.extend_from_slice(&buffer[..read]);
+        let valid = match std::str::from_utf8(&partial) {
+            Ok(text) => text.len(),
+            // Incomplete at the end, so the rest comes with the next read.
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(error) => return Err(io::Error::new(io::ErrorKind::InvalidData, error).into()),
+        };
+        let text = std::str::from_utf8(&partial[..valid]).unwrap_or_default();
+        cipher.update(text, &mut output)?;
+        writer.write_all(output.as_bytes())?;
+        output.clear();
+        partial.drain(..valid);
+    }
+    if !partial.is_empty() {
+        let error = "the input ends in the middle of a character";
+        return Err(io::Error::new(io::ErrorKind::InvalidData, error).into());
+    }
+    cipher.finish(&mut output)?;
+    writer.write_all(output.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Wraps a [`StreamCipher`] so that its output comes out trimmed, as if `str::trim` had been
+/// applied to all of it.
+#[derive(Debug)]
+pub struct Trimmed<C> {
+    cipher: C,
+    started: bool,
+    /// Whitespace that is only written if something other than whitespace follows.
+    whitespace: String,
+    piece: String,
+}
+
+impl<C: StreamCipher> Trimmed<C> {
+    pub fn new(cipher: C) -> Self {
+        Trimmed {
+            cipher,
+            started: false,
+            whitespace: String::new(),
+            piece: String::new(),
+        }
+    }
+
+    fn trim(&mut self, output: &mut String) {
+        for c in self.piece.chars() {
+            if c.is_whitespace() {
+                if self.started {
+                    self.whitespace.push(c);
+                }
+            } else {
+                output.push_str(&self.whitespace);
+                self.whitespace.clear();
+                output.push(c);
+                self.started = true;
+            }
+        }
+        self.piece.clear();
+    }
+}
+
+impl<C: StreamCipher> StreamCipher for Trimmed<C> {
+    fn update(&mut self, input: &str, output: &mut String) -> Result<(), CipherError> {
+        self.cipher.update(input, &mut self.piece)?;
+        self.trim(output);
+        Ok(())
+    }
+
+    fn finish(&mut self, output: &mut String) -> Result<(), CipherError> {
+        self.cipher.finish(&mut self.piece)?;
+        self.trim(output);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Upper cases the text, failing at the first digit.
+    struct Upper(usize);
+
+    impl StreamCipher for Upper {
+        fn update(&mut self, input: &str, output: &mut String) -> Result<(), CipherError> {
+            for c in input.chars() {
+                if c.is_ascii_digit() {
+                    return Err(CipherError::UnsupportedCharacter {
+                        character: c,
+                        position: self.0,
+                    });
+                }
+                output.extend(c.to_uppercase());
+                self.0 += 1;
+            }
+            Ok(())
+        }
+
+        fn finish(&mut self, _output: &mut String) -> Result<(), CipherError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_transform() {
+        // Long enough to be read in several chunks, with characters split between them.
+        let text = "  grüße, ελληνικά ".repeat(CHUNK_SIZE / 7);
+        let mut output = Vec::new();
+        transform(text.as_bytes(), &mut output, &mut Trimmed::new(Upper(0))).unwrap();
+        assert_eq!(
+            text.to_uppercase().trim(),
+            String::from_utf8(output).unwrap()
+        );
+
+        let text = format!("{}4", "a".repeat(CHUNK_SIZE));
+        match transform(text.as_bytes(), io::sink(), &mut Upper(0)) {
+            Err(StreamError::Cipher(CipherError::UnsupportedCharacter { position, .. })) => {
+                assert_eq!(CHUNK_SIZE, position)
+            }
+            result => panic!("{result:?}"),
+        }
+        assert!(matches!(
+            transform(&[b'a', 0xff][..], io::sink(), &mut Upper(0)),
+            Err(StreamError::Io(_))
+        ));
+        assert!(matches!(
+            transform("ü".as_bytes()[..1].as_ref(), io::sink(), &mut Upper(0)),
+            Err(StreamError::Io(_))
+        ));
+    }
+}
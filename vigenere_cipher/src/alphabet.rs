@@ -0,0 +1,230 @@
+//! The ordered set of symbols a Vigenère tableau is built from.
+use utils::cipher::ALPHANUMERIC;
+use utils::error::CipherError;
+
+/// An ordered set of distinct symbols. Shifting by `k` moves a symbol `k` places along it,
+/// wrapping around at the end, so the tableau works modulo [`Alphabet::len`].
+///
+/// Alphabets without lower case letters match their input case insensitively.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alphabet {
+    symbols: Vec<char>,
+    case_sensitive: bool,
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        Alphabet::alphanumeric()
+    }
+}
+
+impl Alphabet {
+    /// The names accepted by [`Alphabet::from_name`], the default first.
+    pub const NAMES: [&'static str; 3] = ["alphanumeric", "latin", "ascii"];
+
+    /// Builds an alphabet from `symbols` in the order given, e.g. a mixed alphabet exported by
+    /// another tool.
+    pub fn new(symbols: &str) -> Result<Self, CipherError> {
+        let symbols: Vec<char> = symbols.chars().collect();
+        if let Some((i, c)) = symbols
+            .iter()
+            .enumerate()
+            .find(|(i, c)| symbols[..*i].contains(c))
+        {
+            return Err(CipherError::InvalidKey(format!(
+                "{c:?} appears twice in the alphabet, at {} and {i}",
+                symbols.iter().position(|s| s == c).unwrap_or_default()
+            )));
+        }
+        if symbols.len() < 2 {
+            return Err(CipherError::InvalidKey(
+                "the alphabet needs at least two symbols".to_string(),
+            ));
+        }
+        let case_sensitive = symbols.iter().any(|c| c.is_lowercase());
+        Ok(Alphabet {
+            symbols,
+            case_sensitive,
+        })
+    }
+
+    /// `A-Z`, the classic 26 letter tableau.
+    pub fn latin() -> Self {
+        Alphabet::new(&ALPHANUMERIC[..26]).expect("A-Z is a valid alphabet")
+    }
+
+    /// `A-Z` followed by `0-9`.
+    pub fn alphanumeric() -> Self {
+        Alphabet::new(ALPHANUMERIC).expect("A-Z0-9 is a valid alphabet")
+    }
+
+    /// The 95 printable ASCII characters from space to `~`.
+    pub fn printable_ascii() -> Self {
+        let symbols: String = (' '..='~').collect();
+        Alphabet::new(&symbols).expect("printable ASCII is a valid alphabet")
+    }
+
+    /// Looks up one of the built-in alphabets by the names in [`Alphabet::NAMES`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "alphanumeric" => Some(Alphabet::alphanumeric()),
+            "latin" => Some(Alphabet::latin()),
+            "ascii" => Some(Alphabet::printable_ascii()),
+            _ => None,
+        }
+    }
+
+    /// A keyed alphabet: the distinct symbols of `keyword` followed by the rest of `self` in
+    /// order, as used by mixed-alphabet tableaux.
+    pub fn keyed(&self, keyword: &str) -> Result<Self, CipherError> {
+        let mut symbols: Vec<char> = Vec::with_capacity(self.len());
+        for c in keyword.chars() {
+            let index = self
+                .index_of(c)
+                .ok_or_else(|| CipherError::InvalidKey(format!("{c:?} is not in the alphabet")))?;
+            if !symbols.contains(&self.symbols[index]) {
+                symbols.push(self.symbols[index]);
+            }
+        }
+        for &c in &self.symbols {
+            if !symbols.contains(&c) {
+                symbols.push(c);
+            }
+        }
+        Ok(Alphabet {
+            symbols,
+            case_sensitive: self.case_sensitive,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn symbols(&self) -> &[char] {
+        &self.symbols
+    }
+
+    /// Position of `c` in the alphabet.
+    pub fn index_of(&self, c: char) -> Option<usize> {
+        let c = if self.case_sensitive {
+            c
+        } else {
+            c.to_ascii_uppercase()
+        };
+        self.symbols.iter().position(|&s| s == c)
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.index_of(c).is_some()
+    }
+
+    /// Gives `symbol` the case of `original` if the alphabet ignores case, so that lower case
+    /// input stays lower case.
+    pub fn restore_case(&self, original: char, symbol: char) -> char {
+        if !self.case_sensitive && original.is_lowercase() {
+            symbol.to_ascii_lowercase()
+        } else {
+            symbol
+        }
+    }
+
+    /// The alphabet [`crate::variants::encrypt_preserving`] works over. Case insensitive alphabets
+    /// that mix letters with symbols without case, like A-Z0-9, get the lower case letters
+    /// appended, since a digit could not carry the case of the letter it came from.
+    pub fn case_preserving(&self) -> Alphabet {
+        let has_caseless = self.symbols.iter().any(|c| !c.is_alphabetic());
+        if self.case_sensitive || !has_caseless {
+            return self.clone();
+        }
+        let mut symbols = self.symbols.clone();
+        symbols.extend(
+            self.symbols
+                .iter()
+                .filter(|c| c.is_alphabetic())
+                .map(|c| c.to_ascii_lowercase()),
+        );
+        Alphabet {
+            symbols,
+            case_sensitive: true,
+        }
+    }
+
+    /// The symbol at `index`, wrapping around at the end.
+    pub fn symbol(&self, index: usize) -> char {
+        self.symbols[index % self.len()]
+    }
+
+    /// Shifts the symbol at `p` forward by `k` places.
+    pub fn add(&self, p: usize, k: usize) -> usize {
+        (p + k) % self.len()
+    }
+
+    /// Shifts the symbol at `c` back by `k` places.
+    pub fn sub(&self, c: usize, k: usize) -> usize {
+        (c + self.len() - k % self.len()) % self.len()
+    }
+
+    /// Whether whitespace is part of the alphabet, in which case ciphertexts are not grouped.
+    pub fn has_whitespace(&self) -> bool {
+        self.symbols.iter().any(|c| c.is_whitespace())
+    }
+
+    /// The full tableau, row `i` being the alphabet shifted by `i`.
+    pub fn tableau(&self) -> Vec<Vec<char>> {
+        (0..self.len())
+            .map(|i| (0..self.len()).map(|j| self.symbol(i + j)).collect())
+            .collect()
+    }
+
+    /// Writes `symbols` out, in groups of five unless whitespace is part of the alphabet.
+    pub fn group(&self, symbols: &[usize]) -> String {
+        let to_string =
+            |symbols: &[usize]| -> String { symbols.iter().map(|&s| self.symbol(s)).collect() };
+        if self.has_whitespace() {
+            return to_string(symbols);
+        }
+        symbols
+            .chunks(5)
+            .map(to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin() {
+        assert_eq!(26, Alphabet::latin().len());
+        assert_eq!(36, Alphabet::alphanumeric().len());
+        assert_eq!(95, Alphabet::printable_ascii().len());
+        for name in Alphabet::NAMES {
+            assert!(Alphabet::from_name(name).is_some());
+        }
+    }
+
+    #[test]
+    fn test_case() {
+        assert_eq!(Some(1), Alphabet::latin().index_of('b'));
+        assert_eq!(Some(66), Alphabet::printable_ascii().index_of('b'));
+        assert_eq!(Some(34), Alphabet::printable_ascii().index_of('B'));
+    }
+
+    #[test]
+    fn test_keyed() {
+        let keyed = Alphabet::latin().keyed("kryptos").unwrap();
+        assert_eq!(
+            "KRYPTOSABCDEFGHIJLMNQUVWXZ",
+            keyed.symbols().iter().collect::<String>()
+        );
+        assert!(Alphabet::latin().keyed("k9").is_err());
+        assert!(Alphabet::new("ABA").is_err());
+    }
+}
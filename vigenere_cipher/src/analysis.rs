@@ -0,0 +1,391 @@
+//! Estimating the keyword length of ciphertexts produced by [`crate::generate_cipher`] when the
+//! keyword is not known, and recovering the keyword itself.
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use utils::cipher::ALPHANUMERIC;
+
+/// The 36 symbols the cipher works over, in tableau order.
+pub const ALPHABET: &str = ALPHANUMERIC;
+
+/// Index of coincidence of English text reduced to letters and digits.
+pub const ENGLISH_IOC: f64 = 0.0667;
+
+/// Index of coincidence of uniformly random text over [`ALPHABET`].
+pub const RANDOM_IOC: f64 = 1.0 / 36.0;
+
+/// Longest keyword [`break_vigenere`] tries.
+pub const MAX_KEY_LENGTH: usize = 20;
+
+/// How many of the best key lengths from [`estimate_key_length`] [`break_vigenere`] tries.
+const LENGTHS_TO_TRY: usize = 5;
+
+/// Relative frequency of each symbol of [`ALPHABET`] in English text, digits included.
+pub const ENGLISH_FREQUENCIES: [f64; 36] = [
+    0.08126, 0.01486, 0.02768, 0.04231, 0.12638, 0.02216, 0.02006, 0.06062, 0.06930, 0.00152,
+    0.00768, 0.04004, 0.02394, 0.06713, 0.07468, 0.01920, 0.00095, 0.05956, 0.06294, 0.09010,
+    0.02744, 0.00973, 0.02348, 0.00149, 0.01965, 0.00074, 0.00050, 0.00050, 0.00050, 0.00050,
+    0.00050, 0.00050, 0.00050, 0.00050, 0.00050, 0.00050,
+];
+
+/// The most common English bigrams and their relative frequencies, used by [`fitness`]. Every
+/// other pair shares what is left.
+const ENGLISH_BIGRAMS: [(&str, f64); 50] = [
+    ("TH", 0.0356),
+    ("HE", 0.0307),
+    ("IN", 0.0243),
+    ("ER", 0.0205),
+    ("AN", 0.0199),
+    ("RE", 0.0185),
+    ("ON", 0.0176),
+    ("AT", 0.0149),
+    ("EN", 0.0145),
+    ("ND", 0.0135),
+    ("TI", 0.0134),
+    ("ES", 0.0134),
+    ("OR", 0.0128),
+    ("TE", 0.0120),
+    ("OF", 0.0117),
+    ("ED", 0.0117),
+    ("IS", 0.0113),
+    ("IT", 0.0112),
+    ("AL", 0.0109),
+    ("AR", 0.0107),
+    ("ST", 0.0105),
+    ("TO", 0.0104),
+    ("NT", 0.0104),
+    ("NG", 0.0095),
+    ("SE", 0.0093),
+    ("HA", 0.0093),
+    ("AS", 0.0087),
+    ("OU", 0.0087),
+    ("IO", 0.0083),
+    ("LE", 0.0083),
+    ("VE", 0.0083),
+    ("CO", 0.0079),
+    ("ME", 0.0079),
+    ("DE", 0.0076),
+    ("HI", 0.0076),
+    ("RI", 0.0073),
+    ("RO", 0.0073),
+    ("IC", 0.0070),
+    ("NE", 0.0069),
+    ("EA", 0.0069),
+    ("RA", 0.0069),
+    ("CE", 0.0065),
+    ("LI", 0.0062),
+    ("CH", 0.0060),
+    ("LL", 0.0058),
+    ("BE", 0.0058),
+    ("MA", 0.0057),
+    ("SI", 0.0055),
+    ("OM", 0.0055),
+    ("UR", 0.0054),
+];
+
+/// A possible keyword length together with how strongly the statistics point at it. Scores are
+/// only meaningful relative to the other candidates of the same ciphertext.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyLengthCandidate {
+    pub length: usize,
+    pub score: f64,
+}
+
+/// Maps the ciphertext to tableau indices, dropping whitespace and anything outside
+/// [`ALPHABET`].
+pub fn symbols(ciphertext: &str) -> Vec<usize> {
+    ciphertext
+        .chars()
+        .filter_map(|c| ALPHABET.find(c.to_ascii_uppercase()))
+        .collect()
+}
+
+/// The probability that two symbols drawn at random from `text` are equal.
+pub fn index_of_coincidence(text: &[usize]) -> f64 {
+    let n = text.len();
+    if n < 2 {
+        return 0.0;
+    }
+    let mut counts = [0usize; 36];
+    for &symbol in text {
+        counts[symbol] += 1;
+    }
+    let pairs: usize = counts.iter().map(|&c| c * c.saturating_sub(1)).sum();
+    pairs as f64 / (n * (n - 1)) as f64
+}
+
+/// Splits `text` into `length` columns, the symbols enciphered with the same keyword letter.
+pub fn columns(text: &[usize], length: usize) -> Vec<Vec<usize>> {
+    let mut columns = vec![Vec::new(); length];
+    for (i, &symbol) in text.iter().enumerate() {
+        columns[i % length].push(symbol);
+    }
+    columns
+}
+
+/// Friedman's closed form estimate of the keyword length from the overall index of coincidence.
+pub fn friedman_estimate(ciphertext: &str) -> f64 {
+    let text = symbols(ciphertext);
+    let n = text.len() as f64;
+    let ioc = index_of_coincidence(&text);
+    let denominator = (n - 1.0) * ioc - n * RANDOM_IOC + ENGLISH_IOC;
+    if denominator <= 0.0 {
+        return n;
+    }
+    (ENGLISH_IOC - RANDOM_IOC) * n / denominator
+}
+
+/// Scores each length in `1..=max_length` by the average index of coincidence of its columns,
+/// scaled so that random text scores 0 and English scores 1.
+pub fn friedman(ciphertext: &str, max_length: usize) -> Vec<KeyLengthCandidate> {
+    let text = symbols(ciphertext);
+    (1..=max_length.min(text.len() / 2))
+        .map(|length| {
+            let columns = columns(&text, length);
+            let average = columns
+                .iter()
+                .map(|column| index_of_coincidence(column))
+                .sum::<f64>()
+                / length as f64;
+            KeyLengthCandidate {
+                length,
+                score: ((average - RANDOM_IOC) / (ENGLISH_IOC - RANDOM_IOC)).max(0.0),
+            }
+        })
+        .collect()
+}
+
+/// Kasiski examination: finds repeated trigrams and scores each length in `2..=max_length` by how
+/// many of the distances between repeats it divides, over what chance alone would give.
+pub fn kasiski(ciphertext: &str, max_length: usize) -> Vec<KeyLengthCandidate> {
+    let text = symbols(ciphertext);
+    let mut last_seen: HashMap<&[usize], usize> = HashMap::new();
+    let mut spacings = Vec::new();
+    for (i, trigram) in text.windows(3).enumerate() {
+        if let Some(previous) = last_seen.insert(trigram, i) {
+            spacings.push(i - previous);
+        }
+    }
+
+    (2..=max_length)
+        .map(|length| {
+            let score = if spacings.is_empty() {
+                0.0
+            } else {
+                let divisible = spacings
+                    .iter()
+                    .filter(|&&s| s.is_multiple_of(length))
+                    .count();
+                let fraction = divisible as f64 / spacings.len() as f64;
+                let chance = 1.0 / length as f64;
+                ((fraction - chance) / (1.0 - chance)).max(0.0)
+            };
+            KeyLengthCandidate { length, score }
+        })
+        .collect()
+}
+
+/// Combines [`friedman`] and [`kasiski`] into a single ranking of keyword lengths, best first.
+///
+/// Longer keywords get a small penalty so that multiples of the real length, whose columns look
+/// just as English, do not outrank it.
+pub fn estimate_key_length(ciphertext: &str, max_length: usize) -> Vec<KeyLengthCandidate> {
+    let kasiski: HashMap<usize, f64> = kasiski(ciphertext, max_length)
+        .into_iter()
+        .map(|candidate| (candidate.length, candidate.score))
+        .collect();
+    let mut candidates: Vec<KeyLengthCandidate> = friedman(ciphertext, max_length)
+        .into_iter()
+        .map(|candidate| KeyLengthCandidate {
+            length: candidate.length,
+            score: candidate.score + kasiski.get(&candidate.length).copied().unwrap_or_default()
+                - 0.01 * candidate.length as f64,
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates
+}
+
+/// Chi-squared statistic of `column` deciphered with the keyword symbol `shift` against
+/// [`ENGLISH_FREQUENCIES`]. Lower is more English.
+pub fn chi_squared(column: &[usize], shift: usize) -> f64 {
+    let mut counts = [0usize; 36];
+    for &symbol in column {
+        counts[(symbol + 36 - shift) % 36] += 1;
+    }
+    let n = column.len() as f64;
+    counts
+        .iter()
+        .zip(ENGLISH_FREQUENCIES)
+        .map(|(&observed, frequency)| {
+            let expected = frequency * n;
+            (observed as f64 - expected).powi(2) / expected
+        })
+        .sum()
+}
+
+/// Log10 probabilities of every pair of symbols under [`ENGLISH_BIGRAMS`], and the floor given to
+/// the pairs it does not list, built on first use.
+fn bigram_table() -> &'static ([[f64; 36]; 36], f64) {
+    static TABLE: OnceLock<([[f64; 36]; 36], f64)> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let known: f64 = ENGLISH_BIGRAMS.iter().map(|(_, frequency)| frequency).sum();
+        let floor = ((1.0 - known) / (36 * 36 - ENGLISH_BIGRAMS.len()) as f64).log10();
+        let mut table = [[floor; 36]; 36];
+        for (bigram, frequency) in ENGLISH_BIGRAMS {
+            let symbols = symbols(bigram);
+            table[symbols[0]][symbols[1]] = frequency.log10();
+        }
+        (table, floor)
+    })
+}
+
+/// Average log10 probability of the bigrams of `text` under [`ENGLISH_BIGRAMS`]. Higher is more
+/// English; scores of texts of different lengths can be compared.
+pub fn fitness(text: &[usize]) -> f64 {
+    let (table, floor) = bigram_table();
+    if text.len() < 2 {
+        return *floor;
+    }
+    let total: f64 = text.windows(2).map(|pair| table[pair[0]][pair[1]]).sum();
+    total / (text.len() - 1) as f64
+}
+
+/// Shortens a keyword that is a repetition of a shorter one, e.g. `LEMONLEMON` to `LEMON`.
+fn shortest_period(key: &[usize]) -> &[usize] {
+    let period = (1..key.len())
+        .find(|&period| {
+            key.len().is_multiple_of(period) && key[period..] == key[..key.len() - period]
+        })
+        .unwrap_or(key.len());
+    &key[..period]
+}
+
+/// Recovers the keyword of a ciphertext produced by [`crate::generate_cipher`].
+///
+/// For each of the most likely keyword lengths every column gets the shift with the lowest
+/// [`chi_squared`] statistic. The resulting `(keyword, plaintext, score)` candidates are ranked
+/// by the [`fitness`] of their plaintext, best first.
+pub fn break_vigenere(ciphertext: &str) -> Vec<(String, String, f64)> {
+    let text = symbols(ciphertext);
+    let alphabet: Vec<char> = ALPHABET.chars().collect();
+    let mut candidates: Vec<(String, String, f64)> = Vec::new();
+
+    for candidate in estimate_key_length(ciphertext, MAX_KEY_LENGTH)
+        .into_iter()
+        .take(LENGTHS_TO_TRY)
+    {
+        let key: Vec<usize> = columns(&text, candidate.length)
+            .iter()
+            .map(|column| {
+                (0..36)
+                    .min_by(|&a, &b| chi_squared(column, a).total_cmp(&chi_squared(column, b)))
+                    .unwrap_or_default()
+            })
+            .collect();
+        let key = shortest_period(&key);
+        let plaintext: Vec<usize> = text
+            .iter()
+            .enumerate()
+            .map(|(i, &symbol)| (symbol + 36 - key[i % key.len()]) % 36)
+            .collect();
+
+        let keyword: String = key.iter().map(|&symbol| alphabet[symbol]).collect();
+        if candidates
+            .iter()
+            .any(|(existing, _, _)| *existing == keyword)
+        {
+            continue;
+        }
+        let score = fitness(&plaintext);
+        let plaintext = plaintext.iter().map(|&symbol| alphabet[symbol]).collect();
+        candidates.push((keyword, plaintext, score));
+    }
+
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alphabet::Alphabet;
+    use crate::{decrypt, generate_cipher, Vigenere};
+    use utils::cipher::{Cipher, Keys};
+    use utils::generate_keyword_string;
+
+    const SAMPLE: &str = "It was the best of times it was the worst of times it was \
+        the age of wisdom it was the age of foolishness it was the epoch of belief it was the \
+        epoch of incredulity it was the season of light it was the season of darkness it was the \
+        spring of hope it was the winter of despair we had everything before us we had nothing \
+        before us we were all going direct to heaven we were all going direct the other way in \
+        short the period was so far like the present period that some of its noisiest \
+        authorities insisted on its being received for good or for evil in the superlative \
+        degree of comparison only";
+
+    fn encrypt(plaintext: &str, keyword: &str) -> String {
+        let plaintext: String = plaintext
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_uppercase();
+        let keyword_string = generate_keyword_string(keyword, plaintext.len());
+        generate_cipher(plaintext, keyword_string, &Alphabet::default()).unwrap()
+    }
+
+    #[test]
+    fn test_index_of_coincidence() {
+        assert_eq!(1.0, index_of_coincidence(&symbols("AAAA")));
+        assert_eq!(0.0, index_of_coincidence(&symbols("ABCD")));
+    }
+
+    #[test]
+    fn test_estimate_key_length() {
+        let ciphertext = encrypt(SAMPLE, "LEMON");
+        assert_eq!(5, estimate_key_length(&ciphertext, 20)[0].length);
+        let ciphertext = encrypt(SAMPLE, "CIPHER7");
+        assert_eq!(7, estimate_key_length(&ciphertext, 20)[0].length);
+    }
+
+    #[test]
+    fn test_break_vigenere() {
+        let plaintext: String = SAMPLE
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_uppercase();
+        for keyword in ["LEMON", "CIPHER7"] {
+            let (key, recovered, _) = break_vigenere(&encrypt(SAMPLE, keyword))
+                .into_iter()
+                .next()
+                .unwrap();
+            assert_eq!(keyword, key);
+            assert_eq!(plaintext, recovered);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_without_keyword() {
+        let keys = Keys::from([("keyword".to_string(), String::new())]);
+        let ciphertext = encrypt(SAMPLE, "LEMON");
+        assert_eq!(
+            Vigenere.decrypt(&ciphertext, &keys),
+            decrypt(ciphertext, "LEMON".to_string(), &Alphabet::default())
+        );
+        assert!(Vigenere.encrypt("attack at dawn", &keys).is_err());
+    }
+
+    #[test]
+    fn test_fitness() {
+        assert!(fitness(&symbols("THEQUICKBROWNFOX")) > fitness(&symbols("XQZJVKWPQZXJQKVZ")));
+    }
+
+    #[test]
+    fn test_kasiski() {
+        let ciphertext = encrypt(SAMPLE, "LEMON");
+        let best = kasiski(&ciphertext, 20)
+            .into_iter()
+            .max_by(|a, b| a.score.total_cmp(&b.score))
+            .unwrap();
+        assert_eq!(0, best.length % 5);
+    }
+}
@@ -1,128 +1,265 @@
-use serde_json::{json, Value};
-use std::{env, io};
+pub mod alphabet;
+pub mod analysis;
+pub mod stream;
+pub mod variants;
+
+use alphabet::Alphabet;
+use stream::VigenereStream;
+use utils::cipher::{key_value, validate_keys, Cipher, CipherMode, KeyField, KeyKind, Keys};
+use utils::error::CipherError;
+use utils::stream::StreamCipher;
 use utils::*;
-pub fn generate_cipher(plaintext: String, keyword_string: String) -> String {
-    let alphabet: Vec<char> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().collect();
-    let numbers: Vec<char> = "0123456789".chars().collect();
-    let letters: Vec<char> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().collect();
-
-    // Combine alphabet and numbers into a single character set
-    let alphabet: Vec<char> = alphabet.iter().chain(numbers.iter()).copied().collect();
-
-    let mut encrypted_string = String::new();
-    assert_eq!(plaintext.trim().len(), keyword_string.len());
-    for (i, char) in plaintext.chars().enumerate() {
-        if !alphabet.contains(&char.to_ascii_uppercase()) {
+use variants::Variant;
+
+/// Checks that every symbol of the keyword is part of `alphabet`.
+fn validate_keyword(keyword: &str, alphabet: &Alphabet) -> Result<(), CipherError> {
+    if keyword.is_empty() {
+        return Err(CipherError::InvalidKey("the keyword is empty".to_string()));
+    }
+    match keyword.chars().find(|&c| !alphabet.contains(c)) {
+        Some(c) => Err(CipherError::InvalidKey(format!(
+            "{c:?} is not in the alphabet"
+        ))),
+        None => Ok(()),
+    }
+}
+
+pub fn generate_cipher(
+    plaintext: String,
+    keyword_string: String,
+    alphabet: &Alphabet,
+) -> Result<String, CipherError> {
+    let keyword: Vec<char> = keyword_string.chars().collect();
+    let expected = plaintext.chars().count();
+    if keyword.len() != expected {
+        return Err(CipherError::LengthMismatch {
+            expected,
+            found: keyword.len(),
+        });
+    }
+    if expected > 0 {
+        validate_keyword(&keyword_string, alphabet)?;
+    }
+    let mut encrypted = Vec::new();
+    for (char, key_char) in plaintext.chars().zip(keyword) {
+        let Some(index_plaintext) = alphabet.index_of(char) else {
             continue;
-        }
+        };
+        let index_key = alphabet.index_of(key_char).unwrap_or_default();
+        encrypted.push(alphabet.add(index_plaintext, index_key));
+    }
+    Ok(alphabet.group(&encrypted))
+}
 
-        let mut index_plaintext = char
-            .to_string()
-            .bytes()
-            .next()
-            .unwrap()
-            .to_ascii_uppercase();
-        let mut index_key = keyword_string.chars().collect::<Vec<_>>()[i]
-            .to_string()
-            .bytes()
-            .next()
-            .unwrap()
-            .to_ascii_uppercase();
-        if letters.contains(&char.to_ascii_uppercase()) {
-            index_plaintext -= 65;
-        } else {
-            index_plaintext -= 22;
-        }
-        if letters.contains(&(keyword_string.chars().collect::<Vec<_>>()[i]).to_ascii_uppercase()) {
-            index_key -= 65;
+pub fn decrypt(
+    encrypted_string: String,
+    keyword: String,
+    alphabet: &Alphabet,
+) -> Result<String, CipherError> {
+    let keyword = keyword.trim();
+    validate_keyword(keyword, alphabet)?;
+    let key: Vec<usize> = keyword
+        .chars()
+        .filter_map(|c| alphabet.index_of(c))
+        .collect();
+    let mut decrypted_string = String::new();
+    let symbols = encrypted_string
+        .chars()
+        .enumerate()
+        .filter(|&(_, c)| !c.is_whitespace() || alphabet.contains(c));
+    for (i, (position, char)) in symbols.enumerate() {
+        let index_encrypted =
+            alphabet
+                .index_of(char)
+                .ok_or_else(|| CipherError::MalformedCiphertext {
+                    position,
+                    reason: format!("{char:?} is not in the alphabet"),
+                })?;
+        decrypted_string.push(alphabet.symbol(alphabet.sub(index_encrypted, key[i % key.len()])));
+    }
+    Ok(decrypted_string)
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Vigenere;
+
+impl Vigenere {
+    /// The variant picked in `keys`, the classic cipher if none was.
+    fn variant(keys: &Keys) -> Variant {
+        Variant::from_name(key_value(keys, "variant")).unwrap_or(Variant::Vigenere)
+    }
+
+    /// Whether `keys` ask for punctuation, spacing and case to be kept instead of writing the
+    /// ciphertext in groups of five.
+    fn preserves_format(keys: &Keys) -> bool {
+        key_value(keys, "format").eq_ignore_ascii_case("preserve")
+    }
+
+    /// The alphabet picked in `keys`, keyed with the alphabet keyword if one was given.
+    pub fn alphabet(keys: &Keys) -> Result<Alphabet, CipherError> {
+        let alphabet = Alphabet::from_name(key_value(keys, "alphabet")).unwrap_or_default();
+        let mut alphabet_key = key_value(keys, "alphabet_key").to_string();
+        remove_whitespace(&mut alphabet_key);
+        if alphabet_key.is_empty() {
+            Ok(alphabet)
         } else {
-            index_key -= 22;
+            alphabet.keyed(&alphabet_key)
         }
-        let encrypted_letter = alphabet[((index_plaintext + index_key) % 36) as usize];
-        encrypted_string.push(encrypted_letter);
     }
 
-    let mut formatted_string = String::new();
-    for (index, char) in encrypted_string.chars().enumerate() {
-        if index % 5 == 0 {
-            formatted_string.push(' ');
+    /// The keyword in `keys`, without whitespace unless the alphabet contains it.
+    fn keyword(keys: &Keys, alphabet: &Alphabet) -> String {
+        let mut keyword = key_value(keys, "keyword").to_string();
+        if !alphabet.has_whitespace() {
+            remove_whitespace(&mut keyword);
         }
-        formatted_string.push(char);
+        keyword
     }
-    formatted_string
 }
 
-pub fn decrypt(mut encrypted_string: String, keyword: String, ask_for_ai: bool) -> String {
-    let alphabet: Vec<char> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().collect();
-    let numbers: Vec<char> = "0123456789".chars().collect();
-    let letters: Vec<char> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().collect();
+impl Cipher for Vigenere {
+    fn id(&self) -> &'static str {
+        "vigenere"
+    }
+
+    fn name(&self) -> &'static str {
+        "Vigenère Cipher"
+    }
 
-    let alphabet: Vec<char> = alphabet.iter().chain(numbers.iter()).copied().collect();
-    let mut decrypted_string = String::new();
-    // Combine alphabet and numbers into a single character set
-    remove_whitespace(&mut encrypted_string);
-    let keyword_string =
-        generate_keyword_string(&mut keyword.trim().to_string(), encrypted_string.len());
-    for (i, char) in encrypted_string.trim().chars().enumerate() {
-        if !alphabet.contains(&char.to_ascii_uppercase()) {
-            continue;
+    fn description(&self) -> &'static str {
+        "Polyalphabetic substitution driven by a keyword, with Beaufort, autokey and Gronsfeld variants"
+    }
+
+    fn key_schema(&self) -> Vec<KeyField> {
+        vec![
+            KeyField {
+                id: "keyword",
+                label: "Keyword",
+                flag: "key",
+                // Optional so that decryption can fall back to `analysis::break_vigenere`. Which
+                // symbols are allowed depends on the alphabet, so they are checked when running.
+                kind: KeyKind::Text {
+                    charset: "",
+                    min_len: 0,
+                },
+            },
+            KeyField {
+                id: "variant",
+                label: "Variant",
+                flag: "variant",
+                kind: KeyKind::Choice {
+                    options: &Variant::NAMES,
+                },
+            },
+            KeyField {
+                id: "format",
+                label: "Format",
+                flag: "format",
+                kind: KeyKind::Choice {
+                    options: &["grouped", "preserve"],
+                },
+            },
+            KeyField {
+                id: "alphabet",
+                label: "Alphabet",
+                flag: "alphabet",
+                kind: KeyKind::Choice {
+                    options: &Alphabet::NAMES,
+                },
+            },
+            KeyField {
+                id: "alphabet_key",
+                label: "Alphabet keyword (optional, mixes the alphabet)",
+                flag: "alphabet-key",
+                kind: KeyKind::Text {
+                    charset: "",
+                    min_len: 0,
+                },
+            },
+        ]
+    }
+
+    fn encrypt(&self, plaintext: &str, keys: &Keys) -> Result<String, CipherError> {
+        validate_keys(&self.key_schema(), keys)?;
+        let alphabet = Self::alphabet(keys)?;
+        let keyword = Self::keyword(keys, &alphabet);
+        let variant = Self::variant(keys);
+        if Self::preserves_format(keys) {
+            return variants::encrypt_preserving(plaintext, &keyword, variant, &alphabet);
         }
+        if variant != Variant::Vigenere {
+            return variants::encrypt(plaintext, &keyword, variant, &alphabet);
+        }
+        validate_keyword(&keyword, &alphabet)?;
+        let plaintext: String = plaintext
+            .chars()
+            .filter(|&c| alphabet.contains(c))
+            .collect();
+        let keyword_string = generate_keyword_string(&keyword, plaintext.chars().count());
+        generate_cipher(plaintext, keyword_string, &alphabet)
+    }
 
-        let mut index_encrypted: i32 = char
-            .to_string()
-            .bytes()
-            .next()
-            .unwrap()
-            .to_ascii_uppercase()
-            .into();
-        let mut index_key: i32 = keyword_string.chars().collect::<Vec<_>>()[i]
-            .to_string()
-            .bytes()
-            .next()
-            .unwrap()
-            .to_ascii_uppercase()
-            .into();
-        if letters.contains(&char.to_ascii_uppercase()) {
-            index_encrypted -= 65;
-        } else {
-            index_encrypted -= 22;
+    fn decrypt(&self, ciphertext: &str, keys: &Keys) -> Result<String, CipherError> {
+        validate_keys(&self.key_schema(), keys)?;
+        let alphabet = Self::alphabet(keys)?;
+        let mut keyword = Self::keyword(keys, &alphabet);
+        let variant = Self::variant(keys);
+        if Self::preserves_format(keys) {
+            return variants::decrypt_preserving(ciphertext, &keyword, variant, &alphabet);
         }
-        if letters.contains(&(keyword_string.chars().collect::<Vec<_>>()[i]).to_ascii_uppercase()) {
-            index_key -= 65;
-        } else {
-            index_key -= 22;
+        if variant != Variant::Vigenere {
+            return variants::decrypt(ciphertext, &keyword, variant, &alphabet);
         }
-        let decrypted_letter = alphabet[(((index_encrypted + 36) - index_key) % 36) as usize];
-        decrypted_string.push(decrypted_letter);
-    }
-    let url = format!("https://generativelanguage.googleapis.com/v1beta/models/gemini-1.5-flash:generateContent?key={}", env::var("GEMINI_API_KEY_RUST").unwrap_or_default());
-    let client = reqwest::blocking::Client::new();
-    let payload = json!({
-        "contents": [
-            {
-                "parts": [
-                    {"text": format!("Format this paragraph: {} and return ONLY THE PARAGRAPH", decrypted_string)}
-                ]
+        if keyword.is_empty() && !ciphertext.trim().is_empty() {
+            if alphabet != Alphabet::alphanumeric() {
+                return Err(CipherError::InvalidKey(
+                    "the keyword can only be recovered over the alphanumeric alphabet".to_string(),
+                ));
             }
-        ]
-    });
-
-    let mut ai_process = String::new();
-    if ask_for_ai {
-        println!("Use AI to reformat the string? [requires you to set the environment variable GEMINI_API_KEY_RUST to a valid api key] [y/N]");
-        io::stdin()
-            .read_line(&mut ai_process)
-            .expect("Error reading line");
-    }
-    if ai_process.trim() == "y" {
-        let res = client.post(&url).json(&payload).send();
-        let str_res = res
-            .expect("Request failed")
-            .text()
-            .expect("Error reading response content");
-        let json_res: Value =
-            serde_json::from_str(&str_res[..]).expect("Error converting response to json");
-        decrypted_string = json_res["candidates"][0]["content"]["parts"][0]["text"].to_string();
-    }
-    decrypted_string
+            keyword = analysis::break_vigenere(ciphertext)
+                .into_iter()
+                .next()
+                .map(|(keyword, _, _)| keyword)
+                .ok_or_else(|| CipherError::MalformedCiphertext {
+                    position: 0,
+                    reason: "too short to recover the keyword".to_string(),
+                })?;
+        }
+        decrypt(ciphertext.to_string(), keyword, &alphabet)
+    }
+
+    fn key_material(&self, keys: &Keys) -> Option<String> {
+        let alphabet = Self::alphabet(keys).ok()?;
+        let symbols = alphabet.symbols();
+        let row = |header: char, cells: &[char]| -> String {
+            let cells: String = cells.iter().map(|c| format!("{c:>2} ")).collect();
+            format!("{header:>2} | {cells}\n")
+        };
+        let mut table = row(' ', symbols);
+        table.push_str(&format!("---+-{}\n", "---".repeat(symbols.len())));
+        for (header, cells) in symbols.iter().zip(alphabet.tableau()) {
+            table.push_str(&row(*header, &cells));
+        }
+        Some(table)
+    }
+
+    fn stream(
+        &self,
+        mode: CipherMode,
+        keys: &Keys,
+    ) -> Result<Option<Box<dyn StreamCipher>>, CipherError> {
+        validate_keys(&self.key_schema(), keys)?;
+        let alphabet = Self::alphabet(keys)?;
+        let keyword = Self::keyword(keys, &alphabet);
+        let variant = Self::variant(keys);
+        let stream = VigenereStream::new(&keyword, variant, &alphabet, mode);
+        if Self::preserves_format(keys) {
+            return Ok(Some(Box::new(stream?.preserving())));
+        }
+        // Without a keyword the classic cipher is broken, which needs the whole ciphertext.
+        if mode == CipherMode::Decrypt && variant == Variant::Vigenere && keyword.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(stream?)))
+    }
 }
@@ -0,0 +1,201 @@
+//! Encryption and decryption a piece of text at a time, for input too large to load at once.
+use std::collections::VecDeque;
+
+use crate::alphabet::Alphabet;
+use crate::variants::Variant;
+use utils::cipher::CipherMode;
+use utils::error::CipherError;
+use utils::stream::StreamCipher;
+
+/// Runs any [`Variant`] over text fed in pieces, giving the same output as
+/// [`crate::variants::encrypt`] and [`crate::variants::decrypt`], or with
+/// [`VigenereStream::preserving`] as their format preserving counterparts. Only the last
+/// keyword length of symbols is remembered, for the autokey variants.
+#[derive(Debug, Clone)]
+pub struct VigenereStream {
+    alphabet: Alphabet,
+    variant: Variant,
+    mode: CipherMode,
+    key: Vec<usize>,
+    preserving: bool,
+    /// The plaintext or ciphertext symbols the autokey variants take the key stream from.
+    history: VecDeque<usize>,
+    /// Symbols handled so far.
+    count: usize,
+    /// Characters of the input read so far.
+    position: usize,
+}
+
+impl VigenereStream {
+    pub fn new(
+        keyword: &str,
+        variant: Variant,
+        alphabet: &Alphabet,
+        mode: CipherMode,
+    ) -> Result<Self, CipherError> {
+        Ok(VigenereStream {
+            alphabet: alphabet.clone(),
+            variant,
+            mode,
+            key: variant.key_shifts(keyword, alphabet)?,
+            preserving: false,
+            history: VecDeque::new(),
+            count: 0,
+            position: 0,
+        })
+    }
+
+    /// Leaves anything outside the alphabet where it is and keeps the case of letters, see
+    /// [`crate::variants::encrypt_preserving`].
+    pub fn preserving(mut self) -> Self {
+        self.alphabet = self.alphabet.case_preserving();
+        self.preserving = true;
+        self
+    }
+
+    /// Encrypts or decrypts the symbol at `symbol`, moving the key stream on.
+    fn shift(&mut self, symbol: usize) -> char {
+        let k = match self.variant {
+            Variant::Autokey | Variant::CiphertextAutokey if self.count >= self.key.len() => {
+                self.history.pop_front().unwrap_or_default()
+            }
+            _ => self.key[self.count % self.key.len()],
+        };
+        let (result, plaintext, ciphertext) = match self.mode {
+            CipherMode::Encrypt => {
+                let encrypted = self.variant.encrypt_symbol(&self.alphabet, symbol, k);
+                (encrypted, symbol, encrypted)
+            }
+            CipherMode::Decrypt => {
+                let decrypted = self.variant.decrypt_symbol(&self.alphabet, symbol, k);
+                (decrypted, decrypted, symbol)
+            }
+        };
+        match self.variant {
+            Variant::Autokey => self.history.push_back(plaintext),
+            Variant::CiphertextAutokey => self.history.push_back(ciphertext),
+            _ => {}
+        }
+        self.count += 1;
+        self.alphabet.symbol(result)
+    }
+}
+
+impl StreamCipher for VigenereStream {
+    fn update(&mut self, input: &str, output: &mut String) -> Result<(), CipherError> {
+        for c in input.chars() {
+            let position = self.position;
+            self.position += 1;
+            if self.preserving {
+                match self.alphabet.index_of(c) {
+                    Some(symbol) => {
+                        let result = self.shift(symbol);
+                        output.push(self.alphabet.restore_case(c, result));
+                    }
+                    None => output.push(c),
+                }
+                continue;
+            }
+            match self.mode {
+                CipherMode::Encrypt => {
+                    let Some(symbol) = self.alphabet.index_of(c) else {
+                        continue;
+                    };
+                    if self.count > 0
+                        && self.count.is_multiple_of(5)
+                        && !self.alphabet.has_whitespace()
+                    {
+                        output.push(' ');
+                    }
+                    let result = self.shift(symbol);
+                    output.push(result);
+                }
+                CipherMode::Decrypt => {
+                    if c.is_whitespace() && !self.alphabet.contains(c) {
+                        continue;
+                    }
+                    let symbol = self.alphabet.index_of(c).ok_or_else(|| {
+                        CipherError::MalformedCiphertext {
+                            position,
+                            reason: format!("{c:?} is not in the alphabet"),
+                        }
+                    })?;
+                    let result = self.shift(symbol);
+                    output.push(result);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _output: &mut String) -> Result<(), CipherError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants;
+
+    /// Runs `stream` over `text` a few characters at a time.
+    fn run(mut stream: VigenereStream, text: &str) -> Result<String, CipherError> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut output = String::new();
+        for piece in chars.chunks(3) {
+            stream.update(&piece.iter().collect::<String>(), &mut output)?;
+        }
+        stream.finish(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_matches_batch() {
+        let plaintext = "It's 3 o'clock, Mr. Holmes; \"Attack at dawn!\"\n\tÉté.";
+        for alphabet in [
+            Alphabet::latin(),
+            Alphabet::alphanumeric(),
+            Alphabet::printable_ascii(),
+        ] {
+            for variant in Variant::ALL {
+                let keyword = if variant == Variant::Gronsfeld {
+                    "31415"
+                } else {
+                    "LEMON"
+                };
+                let stream = |mode| VigenereStream::new(keyword, variant, &alphabet, mode).unwrap();
+                let ciphertext = variants::encrypt(plaintext, keyword, variant, &alphabet).unwrap();
+                assert_eq!(
+                    ciphertext,
+                    run(stream(CipherMode::Encrypt), plaintext).unwrap()
+                );
+                assert_eq!(
+                    variants::decrypt(&ciphertext, keyword, variant, &alphabet).unwrap(),
+                    run(stream(CipherMode::Decrypt), &ciphertext).unwrap()
+                );
+
+                let preserved =
+                    variants::encrypt_preserving(plaintext, keyword, variant, &alphabet).unwrap();
+                let encrypted = run(stream(CipherMode::Encrypt).preserving(), plaintext).unwrap();
+                assert_eq!(preserved, encrypted);
+                let decrypted = run(stream(CipherMode::Decrypt).preserving(), &preserved).unwrap();
+                assert_eq!(plaintext, decrypted);
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let alphabet = Alphabet::default();
+        let stream =
+            VigenereStream::new("LEMON", Variant::Vigenere, &alphabet, CipherMode::Decrypt);
+        assert_eq!(
+            Err(CipherError::MalformedCiphertext {
+                position: 8,
+                reason: "'!' is not in the alphabet".to_string()
+            }),
+            run(stream.unwrap(), "LXFOP KT!")
+        );
+        assert!(VigenereStream::new("", Variant::Autokey, &alphabet, CipherMode::Encrypt).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+//! Relatives of the Vigenère cipher that share its tableau but combine the key with the text
+//! differently or derive the key stream from the message itself.
+use crate::alphabet::Alphabet;
+use utils::cipher::CipherMode;
+use utils::error::CipherError;
+
+/// The ways the keyword and the text can be combined, in the order they are offered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// The classic additive tableau, `c = p + k`.
+    Vigenere,
+    /// `c = k - p`, which makes encryption and decryption the same operation.
+    Beaufort,
+    /// `c = p - k`, Vigenère decryption used to encrypt.
+    VariantBeaufort,
+    /// Additive, with the keyword followed by the plaintext as the key stream.
+    Autokey,
+    /// Additive, with the keyword followed by the ciphertext as the key stream.
+    CiphertextAutokey,
+    /// Additive with a keyword of digits, each shifting by its value.
+    Gronsfeld,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 6] = [
+        Variant::Vigenere,
+        Variant::Beaufort,
+        Variant::VariantBeaufort,
+        Variant::Autokey,
+        Variant::CiphertextAutokey,
+        Variant::Gronsfeld,
+    ];
+
+    /// The names of [`Variant::ALL`], as used on the command line.
+    pub const NAMES: [&'static str; 6] = [
+        "vigenere",
+        "beaufort",
+        "variant-beaufort",
+        "autokey",
+        "ciphertext-autokey",
+        "gronsfeld",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        Variant::NAMES[Variant::ALL
+            .iter()
+            .position(|v| v == self)
+            .unwrap_or_default()]
+    }
+
+    /// Looks a variant up by its [`Variant::name`], ignoring case.
+    pub fn from_name(name: &str) -> Option<Variant> {
+        Variant::NAMES
+            .iter()
+            .position(|n| n.eq_ignore_ascii_case(name.trim()))
+            .map(|i| Variant::ALL[i])
+    }
+
+    pub(crate) fn encrypt_symbol(&self, alphabet: &Alphabet, p: usize, k: usize) -> usize {
+        match self {
+            Variant::Beaufort => alphabet.sub(k, p),
+            Variant::VariantBeaufort => alphabet.sub(p, k),
+            _ => alphabet.add(p, k),
+        }
+    }
+
+    pub(crate) fn decrypt_symbol(&self, alphabet: &Alphabet, c: usize, k: usize) -> usize {
+        match self {
+            Variant::Beaufort => alphabet.sub(k, c),
+            Variant::VariantBeaufort => alphabet.add(c, k),
+            _ => alphabet.sub(c, k),
+        }
+    }
+
+    /// Turns the keyword into shifts, digits standing for their value in the Gronsfeld cipher
+    /// and for their tableau position everywhere else.
+    pub(crate) fn key_shifts(
+        &self,
+        keyword: &str,
+        alphabet: &Alphabet,
+    ) -> Result<Vec<usize>, CipherError> {
+        if keyword.is_empty() {
+            return Err(CipherError::InvalidKey("the keyword is empty".to_string()));
+        }
+        keyword
+            .chars()
+            .map(|c| match self {
+                Variant::Gronsfeld => c
+                    .to_digit(10)
+                    .map(|d| d as usize)
+                    .ok_or_else(|| CipherError::InvalidKey(format!("{c:?} is not a digit"))),
+                _ => alphabet.index_of(c).ok_or_else(|| {
+                    CipherError::InvalidKey(format!("{c:?} is not in the alphabet"))
+                }),
+            })
+            .collect()
+    }
+}
+
+impl Variant {
+    /// The shift for the `i`th symbol, given the symbols of both texts handled so far.
+    fn key_symbol(
+        &self,
+        key: &[usize],
+        i: usize,
+        plaintext: &[usize],
+        ciphertext: &[usize],
+    ) -> usize {
+        match self {
+            Variant::Autokey if i >= key.len() => plaintext[i - key.len()],
+            Variant::CiphertextAutokey if i >= key.len() => ciphertext[i - key.len()],
+            _ => key[i % key.len()],
+        }
+    }
+}
+
+/// Encrypts `plaintext` with `variant`, skipping anything that is not in `alphabet`.
+pub fn encrypt(
+    plaintext: &str,
+    keyword: &str,
+    variant: Variant,
+    alphabet: &Alphabet,
+) -> Result<String, CipherError> {
+    let key = variant.key_shifts(keyword, alphabet)?;
+    let plaintext: Vec<usize> = plaintext
+        .chars()
+        .filter_map(|c| alphabet.index_of(c))
+        .collect();
+    let mut ciphertext: Vec<usize> = Vec::with_capacity(plaintext.len());
+    for (i, &p) in plaintext.iter().enumerate() {
+        let k = variant.key_symbol(&key, i, &plaintext, &ciphertext);
+        ciphertext.push(variant.encrypt_symbol(alphabet, p, k));
+    }
+    Ok(alphabet.group(&ciphertext))
+}
+
+/// Reverses [`encrypt`]. Whitespace is ignored unless it is part of `alphabet`, any other
+/// character outside `alphabet` is rejected.
+pub fn decrypt(
+    ciphertext: &str,
+    keyword: &str,
+    variant: Variant,
+    alphabet: &Alphabet,
+) -> Result<String, CipherError> {
+    let key = variant.key_shifts(keyword, alphabet)?;
+    let ciphertext: Vec<usize> = ciphertext
+        .chars()
+        .enumerate()
+        .filter(|&(_, c)| !c.is_whitespace() || alphabet.contains(c))
+        .map(|(position, c)| {
+            alphabet
+                .index_of(c)
+                .ok_or_else(|| CipherError::MalformedCiphertext {
+                    position,
+                    reason: format!("{c:?} is not in the alphabet"),
+                })
+        })
+        .collect::<Result<_, _>>()?;
+    let mut plaintext: Vec<usize> = Vec::with_capacity(ciphertext.len());
+    for (i, &c) in ciphertext.iter().enumerate() {
+        let k = variant.key_symbol(&key, i, &plaintext, &ciphertext);
+        plaintext.push(variant.decrypt_symbol(alphabet, c, k));
+    }
+    Ok(plaintext.iter().map(|&p| alphabet.symbol(p)).collect())
+}
+
+/// Encrypts `plaintext` with `variant`, leaving anything that is not in `alphabet` where it is
+/// and keeping the case of letters, so that [`decrypt_preserving`] gives back the exact input.
+/// See [`Alphabet::case_preserving`] for the alphabet the letters are shifted over.
+pub fn encrypt_preserving(
+    plaintext: &str,
+    keyword: &str,
+    variant: Variant,
+    alphabet: &Alphabet,
+) -> Result<String, CipherError> {
+    transform_preserving(plaintext, keyword, variant, alphabet, CipherMode::Encrypt)
+}
+
+/// Reverses [`encrypt_preserving`].
+pub fn decrypt_preserving(
+    ciphertext: &str,
+    keyword: &str,
+    variant: Variant,
+    alphabet: &Alphabet,
+) -> Result<String, CipherError> {
+    transform_preserving(ciphertext, keyword, variant, alphabet, CipherMode::Decrypt)
+}
+
+fn transform_preserving(
+    text: &str,
+    keyword: &str,
+    variant: Variant,
+    alphabet: &Alphabet,
+    mode: CipherMode,
+) -> Result<String, CipherError> {
+    let key = variant.key_shifts(keyword, alphabet)?;
+    let alphabet = &alphabet.case_preserving();
+    let mut plaintext: Vec<usize> = Vec::new();
+    let mut ciphertext: Vec<usize> = Vec::new();
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        let Some(symbol) = alphabet.index_of(c) else {
+            output.push(c);
+            continue;
+        };
+        let k = variant.key_symbol(&key, plaintext.len(), &plaintext, &ciphertext);
+        let result = match mode {
+            CipherMode::Encrypt => {
+                let encrypted = variant.encrypt_symbol(alphabet, symbol, k);
+                plaintext.push(symbol);
+                ciphertext.push(encrypted);
+                encrypted
+            }
+            CipherMode::Decrypt => {
+                let decrypted = variant.decrypt_symbol(alphabet, symbol, k);
+                plaintext.push(decrypted);
+                ciphertext.push(symbol);
+                decrypted
+            }
+        };
+        output.push(alphabet.restore_case(c, alphabet.symbol(result)));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vigenere;
+    use utils::cipher::{Cipher, Keys};
+
+    #[test]
+    fn test_round_trip() {
+        let alphabet = Alphabet::default();
+        for variant in Variant::ALL {
+            let keyword = if variant == Variant::Gronsfeld {
+                "31415"
+            } else {
+                "LEMON7"
+            };
+            let ciphertext = encrypt("Attack at dawn, 0600!", keyword, variant, &alphabet).unwrap();
+            assert_eq!(
+                "ATTACKATDAWN0600",
+                decrypt(&ciphertext, keyword, variant, &alphabet).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_known_ciphertexts() {
+        let alphabet = Alphabet::default();
+        let vigenere = crate::generate_cipher(
+            "ATTACKATDAWN".to_string(),
+            utils::generate_keyword_string("LEMON", 12),
+            &alphabet,
+        )
+        .unwrap();
+        assert_eq!(
+            vigenere,
+            encrypt("ATTACKATDAWN", "LEMON", Variant::Vigenere, &alphabet).unwrap()
+        );
+        // Over A-Z these would be the textbook "LXFOPKTMDCGN" and "LLTOLBETLNPR"; the digits in
+        // the tableau change the wrap-around.
+        assert_eq!(
+            "LX5OP KTCDC 6N",
+            encrypt("ATTACKATDAWN", "LEMON", Variant::Autokey, &alphabet).unwrap()
+        );
+        assert_eq!(
+            "LV3OL BE3LN Z1",
+            encrypt("ATTACKATDAWN", "LEMON", Variant::Beaufort, &alphabet).unwrap()
+        );
+        assert_eq!(
+            "DWWDF NDWGD ZQ",
+            encrypt("ATTACKATDAWN", "3", Variant::Gronsfeld, &alphabet).unwrap()
+        );
+        assert!(encrypt("ATTACK", "LEMON", Variant::Gronsfeld, &alphabet).is_err());
+    }
+
+    #[test]
+    fn test_preserving() {
+        let plaintext = "It's 3 o'clock, Mr. Holmes; \"Attack at dawn!\"\n\tÉté.";
+        for alphabet in [Alphabet::latin(), Alphabet::alphanumeric()] {
+            for variant in Variant::ALL {
+                let keyword = if variant == Variant::Gronsfeld {
+                    "31415"
+                } else {
+                    "LEMON"
+                };
+                let ciphertext =
+                    encrypt_preserving(plaintext, keyword, variant, &alphabet).unwrap();
+                assert_eq!(plaintext.len(), ciphertext.len());
+                assert_eq!(
+                    plaintext,
+                    decrypt_preserving(&ciphertext, keyword, variant, &alphabet).unwrap()
+                );
+            }
+        }
+        assert_eq!(
+            "Lxfopv ef rnhr!",
+            encrypt_preserving(
+                "Attack at dawn!",
+                "LEMON",
+                Variant::Vigenere,
+                &Alphabet::latin()
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_preserve_format() {
+        let keys = Keys::from([
+            ("keyword".to_string(), "LEMON".to_string()),
+            ("format".to_string(), "preserve".to_string()),
+        ]);
+        let plaintext =
+            "Dear Watson,\n\nCome at once if convenient. If inconvenient, come all the same.";
+        let ciphertext = Vigenere.encrypt(plaintext, &keys).unwrap();
+        assert_ne!(plaintext, ciphertext);
+        assert_eq!(plaintext, Vigenere.decrypt(&ciphertext, &keys).unwrap());
+    }
+
+    #[test]
+    fn test_other_alphabets() {
+        let latin = Alphabet::latin();
+        assert_eq!(
+            "LXFOP VEFRN HR",
+            encrypt("attack at dawn", "lemon", Variant::Vigenere, &latin).unwrap()
+        );
+        assert_eq!(
+            "LXFOPKTMDCGN",
+            encrypt("ATTACKATDAWN", "LEMON", Variant::Autokey, &latin)
+                .unwrap()
+                .replace(' ', "")
+        );
+
+        let ascii = Alphabet::printable_ascii();
+        let ciphertext = encrypt("Attack at dawn!", "Lemon", Variant::Vigenere, &ascii).unwrap();
+        assert_eq!(
+            "Attack at dawn!",
+            decrypt(&ciphertext, "Lemon", Variant::Vigenere, &ascii).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_error_positions() {
+        // Positions count every character of the input, including the whitespace skipped.
+        let alphabet = Alphabet::default();
+        let error = Err(CipherError::MalformedCiphertext {
+            position: 8,
+            reason: "'!' is not in the alphabet".to_string(),
+        });
+        for variant in [Variant::Beaufort, Variant::Autokey] {
+            assert_eq!(error, decrypt("LXFOP KT!", "LEMON", variant, &alphabet));
+        }
+        let keys: Keys = [("keyword".to_string(), "LEMON".to_string())]
+            .into_iter()
+            .collect();
+        assert_eq!(error, Vigenere.decrypt("LXFOP KT!", &keys));
+    }
+
+    #[test]
+    fn test_from_name() {
+        for variant in Variant::ALL {
+            assert_eq!(Some(variant), Variant::from_name(variant.name()));
+        }
+        assert_eq!(Some(Variant::Beaufort), Variant::from_name("Beaufort"));
+        assert_eq!(None, Variant::from_name("playfair"));
+    }
+}